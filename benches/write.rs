@@ -35,5 +35,84 @@ fn concurrent_push(crit: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, concurrent_push);
+fn lock_free_push(crit: &mut Criterion) {
+    let mut group = crit.benchmark_group("lock_free_push");
+    for threads in [1, 2, 4, 8, 16] {
+        group.bench_with_input(
+            format!("threads_{threads}"),
+            &threads,
+            |bencher, &n_threads| {
+                bencher.iter(|| {
+                    let lock = Arc::new(grow_lock!(n_threads * 100));
+                    let mut handles = Vec::with_capacity(n_threads);
+                    for _ in 0..threads {
+                        handles.push(thread::spawn({
+                            let lock = Arc::clone(&lock);
+                            move || {
+                                for i in 0..100 {
+                                    lock.push_concurrent(black_box(i))
+                                        .unwrap();
+                                }
+                            }
+                        }));
+                    }
+                    for handle in handles {
+                        handle.join().unwrap();
+                    }
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn guard_push(crit: &mut Criterion) {
+    let mut group = crit.benchmark_group("guard_push");
+    group.bench_function("push", |bencher| {
+        bencher.iter(|| {
+            let lock = grow_lock!(10_000);
+            let mut guard = lock.write().unwrap();
+            for i in 0..10_000 {
+                guard.push(black_box(i));
+            }
+        });
+    });
+    group.finish();
+}
+
+fn bulk_extend(crit: &mut Criterion) {
+    let mut group = crit.benchmark_group("bulk_extend");
+    let values: Vec<usize> = (0..10_000).collect();
+    group.bench_function("extend", |bencher| {
+        bencher.iter(|| {
+            let lock = grow_lock!(values.len());
+            lock.write()
+                .unwrap()
+                .extend(black_box(&values).iter().copied());
+        });
+    });
+    group.bench_function("extend_from_slice", |bencher| {
+        bencher.iter(|| {
+            let lock = grow_lock!(values.len());
+            lock.write().unwrap().extend_from_slice(black_box(&values));
+        });
+    });
+    group.bench_function("extend_from_copy_slice", |bencher| {
+        bencher.iter(|| {
+            let lock = grow_lock!(values.len());
+            lock.write()
+                .unwrap()
+                .extend_from_copy_slice(black_box(&values));
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    concurrent_push,
+    guard_push,
+    lock_free_push,
+    bulk_extend
+);
 criterion_main!(benches);
@@ -0,0 +1,56 @@
+//! Allocators of the buffer of a [`GrowLock`](crate::GrowLock).
+//!
+//! By default, [`Allocator`] is a stable copy of the part of
+//! `core::alloc::Allocator` this crate uses, implemented for [`Global`]
+//! and (with the `std` feature) `System`. A custom allocator implements
+//! it to be used on stable Rust:
+//! ```
+//! # #![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+//! use {
+//!     core::{alloc::Layout, ptr::NonNull},
+//!     growlock::{
+//!         GrowLock,
+//!         allocator::{AllocError, Allocator, Global},
+//!     },
+//! };
+//!
+//! struct Counting;
+//!
+//! // SAFETY: the blocks are allocated by `Global`.
+//! unsafe impl Allocator for Counting {
+//!     fn allocate(
+//!         &self,
+//!         layout: Layout,
+//!     ) -> Result<NonNull<[u8]>, AllocError> {
+//!         println!("allocating {} bytes", layout.size());
+//!         Global.allocate(layout)
+//!     }
+//!     unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+//!         // SAFETY: the block was allocated by `Global`.
+//!         unsafe { Global.deallocate(ptr, layout) }
+//!     }
+//! }
+//!
+//! let lock = GrowLock::with_capacity_in(5, Counting);
+//! lock.write().unwrap().push(1);
+//! ```
+//!
+//! With the `allocator_api` feature (nightly only), these are the
+//! `Allocator` trait and the allocators of the standard library instead,
+//! and the conversions from and to `Vec<T, A>` and `Box<[T], A>` are
+//! available for any allocator (only for [`Global`] otherwise).
+
+#[cfg(not(feature = "allocator_api"))]
+mod stable;
+
+#[cfg(feature = "std")]
+pub use std::alloc::System;
+
+#[cfg(feature = "allocator_api")]
+pub use {
+    alloc::alloc::Global,
+    core::alloc::{AllocError, Allocator},
+};
+
+#[cfg(not(feature = "allocator_api"))]
+pub use self::stable::{AllocError, Allocator, Global};
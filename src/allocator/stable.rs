@@ -0,0 +1,315 @@
+//! Stable copy of the `Allocator` API of the standard library.
+
+#[cfg(feature = "std")]
+use std::alloc::{GlobalAlloc, System};
+
+use {
+    alloc::alloc,
+    core::{
+        alloc::Layout,
+        error::Error,
+        fmt,
+        ptr::{self, NonNull},
+    },
+};
+
+/// The error returned by an [`Allocator`] which could not allocate a
+/// block, as `core::alloc::AllocError`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct AllocError;
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("memory allocation failed")
+    }
+}
+impl Error for AllocError {}
+
+/// An allocator, as `core::alloc::Allocator` (which is only available on
+/// nightly Rust).
+///
+/// Only the methods used by this crate are copied: [`grow`] and
+/// [`shrink`] default to allocating a new block, copying the bytes and
+/// freeing the old block.
+///
+/// # Safety
+/// * the blocks returned by [`allocate`], [`grow`] and [`shrink`] must fit
+///   the requested layout, and stay valid until they are freed (through
+///   this allocator, or a reference to it).
+/// * moving the allocator must not invalidate its blocks.
+///
+/// [`allocate`]: Allocator::allocate
+/// [`grow`]: Allocator::grow
+/// [`shrink`]: Allocator::shrink
+pub unsafe trait Allocator {
+    /// Allocates a block of memory fitting `layout`.
+    ///
+    /// # Errors
+    /// Returns an error if the memory is exhausted, or `layout` is not
+    /// supported by the allocator.
+    fn allocate(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError>;
+    /// Frees the block of memory at `ptr`.
+    ///
+    /// # Safety
+    /// `ptr` must be a block currently allocated by this allocator, and
+    /// `layout` must fit it.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+    /// Grows the block of memory at `ptr`, keeping its contents.
+    ///
+    /// # Errors
+    /// Returns an error if the memory is exhausted, or `new_layout` is not
+    /// supported by the allocator. The old block is left untouched.
+    ///
+    /// # Safety
+    /// `ptr` must be a block currently allocated by this allocator,
+    /// `old_layout` must fit it, and `new_layout` must not be smaller than
+    /// `old_layout`. The old block can't be used anymore on success.
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the caller upholds the contract.
+        unsafe { move_block(self, ptr, old_layout, new_layout) }
+    }
+    /// Shrinks the block of memory at `ptr`, keeping the start of its
+    /// contents.
+    ///
+    /// # Errors
+    /// Returns an error if `new_layout` is not supported by the
+    /// allocator. The old block is left untouched.
+    ///
+    /// # Safety
+    /// `ptr` must be a block currently allocated by this allocator,
+    /// `old_layout` must fit it, and `new_layout` must not be larger than
+    /// `old_layout`. The old block can't be used anymore on success.
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the caller upholds the contract.
+        unsafe { move_block(self, ptr, old_layout, new_layout) }
+    }
+}
+
+// SAFETY: the blocks are allocated by `A`.
+unsafe impl<A: Allocator + ?Sized> Allocator for &A {
+    #[inline]
+    fn allocate(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        (**self).allocate(layout)
+    }
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        // SAFETY: the caller upholds the contract.
+        unsafe { (**self).deallocate(ptr, layout) }
+    }
+    #[inline]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the caller upholds the contract.
+        unsafe { (**self).grow(ptr, old_layout, new_layout) }
+    }
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the caller upholds the contract.
+        unsafe { (**self).shrink(ptr, old_layout, new_layout) }
+    }
+}
+
+/// The global memory allocator, registered with `#[global_allocator]`
+/// (or the default one), as `alloc::alloc::Global`.
+///
+/// Its blocks are the ones of `Vec<T>` and `Box<T>`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+// SAFETY: the blocks are allocated by the global allocator.
+unsafe impl Allocator for Global {
+    #[inline]
+    fn allocate(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: `allocate_with` never passes a zero-sized layout.
+        allocate_with(layout, |layout| unsafe { alloc::alloc(layout) })
+    }
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            // SAFETY: the block was allocated with `alloc::alloc`, as it
+            // is not zero-sized.
+            unsafe { alloc::dealloc(ptr.as_ptr(), layout) }
+        }
+    }
+    #[inline]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the caller upholds the contract, and `realloc_with`
+        // only passes a block allocated with `alloc::alloc`.
+        unsafe {
+            realloc_with(self, ptr, old_layout, new_layout, |ptr, l, s| {
+                alloc::realloc(ptr, l, s)
+            })
+        }
+    }
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: see `grow`.
+        unsafe {
+            realloc_with(self, ptr, old_layout, new_layout, |ptr, l, s| {
+                alloc::realloc(ptr, l, s)
+            })
+        }
+    }
+}
+
+// SAFETY: the blocks are allocated by `System`.
+#[cfg(feature = "std")]
+unsafe impl Allocator for System {
+    #[inline]
+    fn allocate(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: `allocate_with` never passes a zero-sized layout.
+        allocate_with(layout, |layout| unsafe { self.alloc(layout) })
+    }
+    #[inline]
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            // SAFETY: the block was allocated with `System::alloc`, as it
+            // is not zero-sized.
+            unsafe { self.dealloc(ptr.as_ptr(), layout) }
+        }
+    }
+    #[inline]
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: the caller upholds the contract, and `realloc_with`
+        // only passes a block allocated with `System::alloc`.
+        unsafe {
+            realloc_with(self, ptr, old_layout, new_layout, |ptr, l, s| {
+                self.realloc(ptr, l, s)
+            })
+        }
+    }
+    #[inline]
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, AllocError> {
+        // SAFETY: see `grow`.
+        unsafe {
+            realloc_with(self, ptr, old_layout, new_layout, |ptr, l, s| {
+                self.realloc(ptr, l, s)
+            })
+        }
+    }
+}
+
+/// Allocates a block with the `alloc` function of a `GlobalAlloc`, which
+/// doesn't support zero-sized blocks: those are dangling instead.
+#[inline]
+fn allocate_with(
+    layout: Layout,
+    alloc: impl FnOnce(Layout) -> *mut u8,
+) -> Result<NonNull<[u8]>, AllocError> {
+    let ptr = if layout.size() == 0 {
+        // SAFETY: the alignment is not zero.
+        unsafe {
+            NonNull::new_unchecked(ptr::without_provenance_mut(
+                layout.align(),
+            ))
+        }
+    } else {
+        NonNull::new(alloc(layout)).ok_or(AllocError)?
+    };
+    Ok(NonNull::slice_from_raw_parts(ptr, layout.size()))
+}
+
+/// Resizes a block with the `realloc` function of a `GlobalAlloc`, which
+/// only supports non zero-sized blocks keeping their alignment: the other
+/// blocks are moved with [`move_block`].
+///
+/// # Safety
+/// As [`Allocator::grow`] (or [`Allocator::shrink`]), and `realloc` must
+/// be the one of the allocator of `alloc`.
+#[inline]
+unsafe fn realloc_with<A: Allocator + ?Sized>(
+    alloc: &A,
+    ptr: NonNull<u8>,
+    old_layout: Layout,
+    new_layout: Layout,
+    realloc: impl FnOnce(*mut u8, Layout, usize) -> *mut u8,
+) -> Result<NonNull<[u8]>, AllocError> {
+    if old_layout.size() == 0
+        || new_layout.size() == 0
+        || old_layout.align() != new_layout.align()
+    {
+        // SAFETY: the caller upholds the contract.
+        return unsafe { move_block(alloc, ptr, old_layout, new_layout) };
+    }
+    let ptr = realloc(ptr.as_ptr(), old_layout, new_layout.size());
+    let ptr = NonNull::new(ptr).ok_or(AllocError)?;
+    Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()))
+}
+
+/// Moves a block into a new block of `new_layout`, copying as many bytes
+/// as fit in both.
+///
+/// # Safety
+/// `ptr` must be a block currently allocated by `alloc`, and
+/// `old_layout` must fit it.
+unsafe fn move_block<A: Allocator + ?Sized>(
+    alloc: &A,
+    ptr: NonNull<u8>,
+    old_layout: Layout,
+    new_layout: Layout,
+) -> Result<NonNull<[u8]>, AllocError> {
+    let block = alloc.allocate(new_layout)?;
+    // SAFETY: both blocks are valid for `size` bytes, and they don't
+    // overlap as the old one is still allocated. The old block is freed
+    // once, and never used again.
+    unsafe {
+        let size = old_layout.size().min(new_layout.size());
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            block.cast().as_ptr(),
+            size,
+        );
+        alloc.deallocate(ptr, old_layout);
+    }
+    Ok(block)
+}
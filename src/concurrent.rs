@@ -0,0 +1,259 @@
+//! Bookkeeping of the lock-free producers of a
+//! [`GrowLock`](crate::GrowLock).
+//!
+//! Producers calling [`GrowLock::push_concurrent`] don't take the write
+//! lock:
+//! 1. each producer reserves a slot with a fetch-add on `reserved`,
+//! 2. writes its value in the slot,
+//! 3. marks the slot as written in the `published` bitmap,
+//! 4. and then advances `len` over the contiguous prefix of written slots,
+//!    on behalf of all the producers (see [`Producers::publish`]).
+//!
+//! A [`GrowGuard`](crate::guard::GrowGuard) and the producers never write
+//! at the same time: creating a guard raises the [`WRITER`] flag and waits
+//! for the in-flight producers to finish, while a producer that sees the
+//! flag falls back to the guard itself. This way the guard keeps its
+//! exclusive access to the end of the buffer.
+//!
+//! [`GrowLock::push_concurrent`]: crate::GrowLock::push_concurrent
+
+#[cfg(not(loom))]
+use core::{
+    hint,
+    sync::atomic::{self, AtomicPtr, AtomicUsize, Ordering},
+};
+
+#[cfg(loom)]
+use loom::{
+    hint,
+    sync::atomic::{self, AtomicPtr, AtomicUsize, Ordering},
+};
+use {
+    alloc::boxed::Box,
+    core::{ptr, slice},
+};
+
+/// Bits in a word of the `published` bitmap.
+const BITS: usize = usize::BITS as usize;
+/// Flag of `count` raised while a guard is alive.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// Shared state of the lock-free producers of a
+/// [`GrowLock`](crate::GrowLock).
+pub(crate) struct Producers {
+    /// Number of in-flight producers, plus the [`WRITER`] flag.
+    count: AtomicUsize,
+    /// Index of the next slot to reserve. This can overshoot the capacity
+    /// when producers find the lock full.
+    ///
+    /// This is only meaningful when no guard is alive: the guard
+    /// resynchronizes it with `len` when it is dropped.
+    reserved: AtomicUsize,
+    /// One bit for each slot, set when the slot is written. Allocated
+    /// the first time a producer needs it, and freed on drop.
+    published: AtomicPtr<AtomicUsize>,
+    /// Length of the `published` bitmap.
+    words: usize,
+}
+
+impl Producers {
+    /// Creates the producers state of a lock with the given length and
+    /// capacity.
+    pub(crate) fn new(len: usize, capacity: usize) -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            reserved: AtomicUsize::new(len),
+            published: AtomicPtr::new(ptr::null_mut()),
+            // ZSTs (with a capacity of `usize::MAX`) never use the bitmap.
+            words: if capacity == usize::MAX {
+                0
+            } else {
+                capacity.div_ceil(BITS)
+            },
+        }
+    }
+
+    /// Registers an in-flight producer. Returns `false` if a guard is
+    /// alive, in which case the producer is not registered.
+    #[inline]
+    pub(crate) fn enter(&self) -> bool {
+        let mut count = self.count.load(Ordering::Relaxed);
+        loop {
+            if count & WRITER != 0 {
+                return false;
+            }
+            // Acquire: pairs with the Release of `unlock_writer`, so the
+            // `reserved` stored by the guard is visible.
+            match self.count.compare_exchange_weak(
+                count,
+                count + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => count = actual,
+            }
+        }
+    }
+    /// Unregisters an in-flight producer.
+    #[inline]
+    pub(crate) fn leave(&self) {
+        // Release: pairs with the Acquire of `lock_writer`, so the guard
+        // sees everything this producer did.
+        self.count.fetch_sub(1, Ordering::Release);
+    }
+    /// Raises the [`WRITER`] flag and waits for the in-flight producers to
+    /// finish. Must only be called while holding the write lock.
+    pub(crate) fn lock_writer(&self) {
+        self.count.fetch_or(WRITER, Ordering::Acquire);
+        // producers are short-lived: they never block while registered.
+        while self.count.load(Ordering::Acquire) != WRITER {
+            hint::spin_loop();
+        }
+    }
+    /// Lowers the [`WRITER`] flag, restarting the reservations at `len`.
+    pub(crate) fn unlock_writer(&self, len: usize) {
+        self.reserved.store(len, Ordering::Relaxed);
+        self.count.fetch_and(!WRITER, Ordering::Release);
+    }
+
+    /// Reserves the next slot. The returned index may exceed the
+    /// capacity, meaning that the lock is full.
+    #[inline]
+    pub(crate) fn reserve(&self) -> usize {
+        // the `len` published on the reserved slot is ordered by the
+        // bitmap, so this can be Relaxed.
+        self.reserved.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Marks the slot `index` as written, and advances `len` over the
+    /// contiguous prefix of written slots.
+    ///
+    /// # Safety
+    /// * `index` must have been reserved by this producer, and be less
+    ///   than `capacity`, the capacity of the lock.
+    /// * the slot must be initialized.
+    pub(crate) unsafe fn publish(
+        &self,
+        index: usize,
+        capacity: usize,
+        len: &AtomicUsize,
+    ) {
+        let bitmap = self.bitmap();
+        // Release: pairs with the Acquire of `end_of_run`, so whoever
+        // advances `len` over this slot sees its value.
+        bitmap[index / BITS]
+            .fetch_or(1 << (index % BITS), Ordering::Release);
+
+        loop {
+            // a producer that sets its bit (or advances `len`) and then
+            // sees an unwritten slot relies on the producer of that slot
+            // to see its write later. This is the "store
+            // buffering" pattern: only a SeqCst fence between
+            // the write and the reads forbids both of them to
+            // miss the write of the other.
+            atomic::fence(Ordering::SeqCst);
+            let current = len.load(Ordering::Relaxed);
+            let end = Self::end_of_run(bitmap, current, capacity);
+            if end == current {
+                // either everything is published, or the producer of
+                // `current` will continue from here.
+                return;
+            }
+            // Release: publishes the slots observed in the bitmap. On
+            // success, other slots may have been written meanwhile, so we
+            // check again in both cases.
+            let _ = len.compare_exchange(
+                current,
+                end,
+                Ordering::Release,
+                Ordering::Relaxed,
+            );
+        }
+    }
+    /// Returns the end of the run of written slots starting at `start`.
+    fn end_of_run(
+        bitmap: &[AtomicUsize],
+        start: usize,
+        capacity: usize,
+    ) -> usize {
+        let mut end = start;
+        while end < capacity {
+            let offset = end % BITS;
+            let word =
+                bitmap[end / BITS].load(Ordering::Acquire) >> offset;
+            let ones = word.trailing_ones() as usize;
+            end += ones;
+            if offset + ones < BITS {
+                break;
+            }
+        }
+        // slots past the capacity are never written.
+        end.min(capacity)
+    }
+    /// Returns the `published` bitmap, allocating it if needed.
+    fn bitmap(&self) -> &[AtomicUsize] {
+        let mut bitmap = self.published.load(Ordering::Acquire);
+        if bitmap.is_null() {
+            let new: Box<[AtomicUsize]> =
+                (0..self.words).map(|_| AtomicUsize::new(0)).collect();
+            let new = Box::into_raw(new).cast::<AtomicUsize>();
+            // AcqRel: publishes the zeroed bitmap, or acquires the one of
+            // the other producer.
+            match self.published.compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => bitmap = new,
+                Err(current) => {
+                    // SAFETY: `new` was never shared with anyone.
+                    drop(unsafe {
+                        Box::from_raw(ptr::slice_from_raw_parts_mut(
+                            new, self.words,
+                        ))
+                    });
+                    bitmap = current;
+                }
+            }
+        }
+        // SAFETY: the bitmap is allocated with `words` elements, and it is
+        // freed only when `self` is dropped.
+        unsafe { slice::from_raw_parts(bitmap, self.words) }
+    }
+}
+
+impl Drop for Producers {
+    fn drop(&mut self) {
+        let bitmap = self.published.load(Ordering::Acquire);
+        if !bitmap.is_null() {
+            // SAFETY: the bitmap was allocated in `Producers::bitmap` with
+            // `words` elements.
+            drop(unsafe {
+                Box::from_raw(ptr::slice_from_raw_parts_mut(
+                    bitmap, self.words,
+                ))
+            });
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn end_of_run() {
+        let bitmap =
+            [AtomicUsize::new(0b1011), AtomicUsize::new(usize::MAX)];
+        assert_eq!(Producers::end_of_run(&bitmap, 0, 128), 2);
+        assert_eq!(Producers::end_of_run(&bitmap, 2, 128), 2);
+        assert_eq!(Producers::end_of_run(&bitmap, 3, 128), 4);
+
+        let bitmap =
+            [AtomicUsize::new(usize::MAX << 60), AtomicUsize::new(0b111)];
+        assert_eq!(Producers::end_of_run(&bitmap, 60, 128), 67);
+        assert_eq!(Producers::end_of_run(&bitmap, 60, 65), 65);
+    }
+}
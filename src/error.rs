@@ -1,13 +1,13 @@
-use {std::alloc::Layout, thiserror::Error};
+use core::{alloc::Layout, error::Error, fmt};
+#[cfg(feature = "std")]
+pub use std::sync::{
+    LockResult, PoisonError, TryLockError, TryLockResult,
+};
 
-/// Error type for `try_with_capacity` methods.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Error)]
+/// Error type for `try_with_capacity` and `try_reserve` methods.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TryReserveError {
-    #[error("memory allocation failed because capacity exceeded maximum")]
     CapacityOverflow,
-    #[error(
-        "memory allocation failed because allocator returned an error"
-    )]
     AllocError(Layout),
 }
 impl From<Layout> for TryReserveError {
@@ -16,10 +16,168 @@ impl From<Layout> for TryReserveError {
         Self::AllocError(e)
     }
 }
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CapacityOverflow => {
+                "memory allocation failed because capacity exceeded maximum"
+            }
+            Self::AllocError(_) => {
+                "memory allocation failed because allocator returned an \
+                 error"
+            }
+        })
+    }
+}
+impl Error for TryReserveError {}
 
 /// Error type for `try_push` method.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Error)]
-#[error(
-    "tried to push to the `GrowLock`, but the `GrowLock` is already full"
-)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct LengthError;
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(
+            "tried to push to the `GrowLock`, but the `GrowLock` is \
+             already full",
+        )
+    }
+}
+impl Error for LengthError {}
+
+/// Error type for `try_from_vec_with_capacity` method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FromVecError {
+    /// The `Vec` has more elements than the requested capacity.
+    TooManyElements { len: usize, capacity: usize },
+    /// The buffer could not be reallocated to the requested capacity.
+    Reserve(TryReserveError),
+}
+impl From<TryReserveError> for FromVecError {
+    #[inline]
+    fn from(e: TryReserveError) -> Self {
+        Self::Reserve(e)
+    }
+}
+impl fmt::Display for FromVecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyElements { len, capacity } => write!(
+                f,
+                "the `Vec` has {len} elements, which don't fit in a \
+                 capacity of {capacity}"
+            ),
+            Self::Reserve(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+impl Error for FromVecError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::TooManyElements { .. } => None,
+            Self::Reserve(e) => Some(e),
+        }
+    }
+}
+
+/// A type of error which can be returned whenever a lock is acquired.
+///
+/// Without `std`, this replaces `std::sync::PoisonError` (which is
+/// re-exported with `std`). A panic can't be detected without `std`, so
+/// the locks of this crate are never poisoned in this case.
+#[cfg(not(feature = "std"))]
+pub struct PoisonError<T> {
+    data: T,
+}
+
+/// An enumeration of possible errors returned by the `try_write` methods.
+///
+/// Without `std`, this replaces `std::sync::TryLockError` (which is
+/// re-exported with `std`).
+#[cfg(not(feature = "std"))]
+pub enum TryLockError<T> {
+    /// The lock could not be acquired because another writer panicked
+    /// while holding it.
+    Poisoned(PoisonError<T>),
+    /// The lock could not be acquired at this time because the operation
+    /// would otherwise block.
+    WouldBlock,
+}
+
+/// The result of a blocking lock acquisition, as
+/// `std::sync::LockResult`.
+#[cfg(not(feature = "std"))]
+pub type LockResult<T> = Result<T, PoisonError<T>>;
+/// The result of a non-blocking lock acquisition, as
+/// `std::sync::TryLockResult`.
+#[cfg(not(feature = "std"))]
+pub type TryLockResult<T> = Result<T, TryLockError<T>>;
+
+#[cfg(not(feature = "std"))]
+impl<T> PoisonError<T> {
+    /// Creates a [`PoisonError`].
+    #[inline]
+    pub const fn new(data: T) -> Self {
+        Self { data }
+    }
+    /// Consumes this error, returning the underlying guard.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.data
+    }
+    /// Reaches into this error, returning a reference to the underlying
+    /// guard.
+    #[inline]
+    pub const fn get_ref(&self) -> &T {
+        &self.data
+    }
+    /// Reaches into this error, returning a mutable reference to the
+    /// underlying guard.
+    #[inline]
+    pub const fn get_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T> fmt::Debug for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T> fmt::Display for PoisonError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("poisoned lock: another task failed inside")
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T> Error for PoisonError<T> {}
+
+#[cfg(not(feature = "std"))]
+impl<T> From<PoisonError<T>> for TryLockError<T> {
+    #[inline]
+    fn from(err: PoisonError<T>) -> Self {
+        Self::Poisoned(err)
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T> fmt::Debug for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned(..) => "Poisoned(..)".fmt(f),
+            Self::WouldBlock => "WouldBlock".fmt(f),
+        }
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T> fmt::Display for TryLockError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poisoned(err) => fmt::Display::fmt(err, f),
+            Self::WouldBlock => f.write_str(
+                "try_lock failed because the operation would block",
+            ),
+        }
+    }
+}
+#[cfg(not(feature = "std"))]
+impl<T> Error for TryLockError<T> {}
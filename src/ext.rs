@@ -0,0 +1,37 @@
+//! Extension traits converting the collections of the standard library
+//! into a [`GrowLock`].
+
+use {crate::GrowLock, alloc::vec::Vec};
+
+/// Extension methods of [`Vec`] converting it into a [`GrowLock`].
+///
+/// # Examples
+/// ```
+/// use growlock::ext::VecExt;
+///
+/// let lock = vec![1, 2].into_growlock_with_spare(3);
+/// assert_eq!(&lock[..], &[1, 2]);
+/// assert!(lock.capacity() >= 5);
+/// lock.write().unwrap().extend([3, 4, 5]);
+/// ```
+pub trait VecExt<T> {
+    /// Reserves room for at least `additional` more elements (as
+    /// [`Vec::reserve_exact`]), and converts the [`Vec`] into a
+    /// [`GrowLock`] keeping its buffer.
+    ///
+    /// # Panics
+    /// Panics if the new capacity exceeds `isize::MAX` _bytes_.
+    #[must_use]
+    fn into_growlock_with_spare(self, additional: usize) -> GrowLock<T>;
+}
+
+impl<T> VecExt<T> for Vec<T> {
+    #[inline]
+    fn into_growlock_with_spare(
+        mut self,
+        additional: usize,
+    ) -> GrowLock<T> {
+        self.reserve_exact(additional);
+        GrowLock::from(self)
+    }
+}
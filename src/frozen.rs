@@ -0,0 +1,294 @@
+//! A [`GrowLock`](crate::GrowLock) which can't be written anymore.
+//!
+//! Once all the writes are done, [`GrowLock::freeze`] turns the lock into
+//! a [`FrozenGrowLock`]: it keeps the same buffer (nothing is copied), but
+//! drops all the state needed by the writers.
+//!
+//! [`GrowLock::freeze`]: crate::GrowLock::freeze
+
+use {
+    crate::{
+        allocator::{Allocator, Global},
+        raw::RawGrowLock,
+    },
+    alloc::{boxed::Box, sync::Arc, vec::Vec},
+    core::{
+        borrow::Borrow,
+        fmt,
+        hash::{Hash, Hasher},
+        mem::ManuallyDrop,
+        ops, ptr, slice,
+    },
+};
+
+/// An immutable, contiguous array, created by freezing a
+/// [`GrowLock`](crate::GrowLock).
+///
+/// Unlike a [`GrowLock`](crate::GrowLock), it has no write lock, and it
+/// derefs to a plain slice.
+///
+/// # Examples
+/// ```
+/// use growlock::grow_lock;
+///
+/// let lock = grow_lock!(10, [1, 2, 3]);
+/// let frozen = lock.freeze();
+/// assert_eq!(&frozen[..], &[1, 2, 3]);
+/// // the capacity of the lock is kept, the elements are not moved.
+/// assert_eq!(frozen.capacity(), 10);
+/// ```
+pub struct FrozenGrowLock<T, A: Allocator = Global> {
+    buf: RawGrowLock<T, A>,
+    len: usize,
+}
+
+// SAFETY: a `FrozenGrowLock` owns its elements and its allocator, like a
+// `Box<[T], A>`.
+unsafe impl<T: Send, A: Allocator + Send> Send for FrozenGrowLock<T, A> {}
+// SAFETY: a `FrozenGrowLock` has no interior mutability.
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for FrozenGrowLock<T, A> {}
+
+impl<T, A: Allocator> FrozenGrowLock<T, A> {
+    /// Creates a [`FrozenGrowLock`] from its buffer, with the first `len`
+    /// elements initialized.
+    #[inline]
+    pub(crate) const fn from_buf(
+        buf: RawGrowLock<T, A>,
+        len: usize,
+    ) -> Self {
+        Self { buf, len }
+    }
+    #[inline]
+    #[must_use]
+    pub const fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialized, and they are
+        // never mutated.
+        unsafe { slice::from_raw_parts(self.buf.as_ptr(), self.len) }
+    }
+    /// Returns the capacity of the buffer, which is the capacity of the
+    /// [`GrowLock`](crate::GrowLock) it was frozen from.
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn allocator(&self) -> &A {
+        self.buf.allocator()
+    }
+    /// Converts `self` into a boxed slice, shrinking its buffer to fit in
+    /// the same allocator.
+    ///
+    /// Without the `allocator_api` feature, this is only available with
+    /// the [`Global`] allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let frozen = grow_lock!(10, [1, 2, 3]).freeze();
+    /// let boxed: Box<[i32]> = frozen.into_boxed_slice();
+    /// assert_eq!(&*boxed, &[1, 2, 3]);
+    /// ```
+    #[cfg(feature = "allocator_api")]
+    #[inline]
+    #[must_use]
+    pub fn into_boxed_slice(self) -> Box<[T], A> {
+        Vec::from(self).into_boxed_slice()
+    }
+    /// Decomposes `self` into its raw components: (pointer, length,
+    /// capacity, allocator).
+    fn into_raw_parts_with_alloc(self) -> (*mut T, usize, usize, A) {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used again, so the allocator is moved.
+        let alloc = unsafe { ptr::read(this.allocator()) };
+        (this.buf.as_mut_ptr(), this.len, this.capacity(), alloc)
+    }
+}
+
+/// [`Global`] only methods
+#[cfg(not(feature = "allocator_api"))]
+impl<T> FrozenGrowLock<T> {
+    /// Converts `self` into a boxed slice, shrinking its buffer to fit.
+    ///
+    /// With the `allocator_api` feature, this is available with any
+    /// allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let frozen = grow_lock!(10, [1, 2, 3]).freeze();
+    /// let boxed: Box<[i32]> = frozen.into_boxed_slice();
+    /// assert_eq!(&*boxed, &[1, 2, 3]);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn into_boxed_slice(self) -> Box<[T]> {
+        Vec::from(self).into_boxed_slice()
+    }
+}
+
+impl<T, A: Allocator> Drop for FrozenGrowLock<T, A> {
+    fn drop(&mut self) {
+        // SAFETY: the first `len` elements are initialized, and they are
+        // never used again. The buffer is freed by `RawGrowLock`.
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                self.buf.as_mut_ptr(),
+                self.len,
+            ));
+        }
+    }
+}
+
+impl<T, A: Allocator> ops::Deref for FrozenGrowLock<T, A> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+impl<T, A: Allocator> Borrow<[T]> for FrozenGrowLock<T, A> {
+    #[inline]
+    fn borrow(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+impl<T, A: Allocator> AsRef<[T]> for FrozenGrowLock<T, A> {
+    #[inline]
+    fn as_ref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+impl<'a, T, A: Allocator> IntoIterator for &'a FrozenGrowLock<T, A> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for FrozenGrowLock<T, A> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T, U, A, A2> PartialEq<FrozenGrowLock<U, A2>> for FrozenGrowLock<T, A>
+where
+    T: PartialEq<U>,
+    A: Allocator,
+    A2: Allocator,
+{
+    #[inline]
+    fn eq(&self, rhs: &FrozenGrowLock<U, A2>) -> bool {
+        PartialEq::eq(&**self, &**rhs)
+    }
+}
+impl<T: Eq, A: Allocator> Eq for FrozenGrowLock<T, A> {}
+impl<T: Hash, A: Allocator> Hash for FrozenGrowLock<T, A> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Hash::hash(&**self, state);
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T, A: Allocator> From<FrozenGrowLock<T, A>> for Vec<T, A> {
+    #[inline]
+    fn from(value: FrozenGrowLock<T, A>) -> Self {
+        let (ptr, len, cap, alloc) = value.into_raw_parts_with_alloc();
+        // SAFETY: the buffer was allocated with `alloc` and the capacity
+        // of the `Vec` the `GrowLock` was made of, and its first `len`
+        // elements are initialized.
+        unsafe { Self::from_raw_parts_in(ptr, len, cap, alloc) }
+    }
+}
+#[cfg(not(feature = "allocator_api"))]
+impl<T> From<FrozenGrowLock<T>> for Vec<T> {
+    #[inline]
+    fn from(value: FrozenGrowLock<T>) -> Self {
+        let (ptr, len, cap, Global) = value.into_raw_parts_with_alloc();
+        // SAFETY: the buffer was allocated by the global allocator with
+        // this capacity, and its first `len` elements are initialized.
+        unsafe { Self::from_raw_parts(ptr, len, cap) }
+    }
+}
+impl<T> From<FrozenGrowLock<T>> for Box<[T]> {
+    #[inline]
+    fn from(value: FrozenGrowLock<T>) -> Self {
+        value.into_boxed_slice()
+    }
+}
+impl<T> From<FrozenGrowLock<T>> for Arc<[T]> {
+    /// Moves the elements into a new [`Arc<[T]>`](Arc).
+    ///
+    /// An [`Arc`] stores its reference counts before its elements, so it
+    /// can't reuse the buffer: the elements are moved (i.e. copied
+    /// bitwise, without cloning them) once.
+    #[inline]
+    fn from(value: FrozenGrowLock<T>) -> Self {
+        Vec::from(value).into()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use {
+        crate::{GrowLock, grow_lock},
+        std::sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    #[test]
+    fn freeze() {
+        let lock = grow_lock!(5, [String::from("a"), String::from("b")]);
+        let ptr = lock.as_ptr();
+        let frozen = lock.freeze();
+        assert_eq!(frozen.as_ptr(), ptr);
+        assert_eq!(frozen.capacity(), 5);
+        assert_eq!(&frozen[..], &["a", "b"]);
+
+        let boxed = frozen.into_boxed_slice();
+        assert_eq!(&*boxed, &["a", "b"]);
+
+        let lock = GrowLock::with_capacity(3);
+        lock.write().unwrap().extend([1, 2]);
+        let vec = Vec::from(lock.freeze());
+        assert_eq!((vec.as_slice(), vec.capacity()), (&[1, 2][..], 3));
+    }
+
+    #[test]
+    fn freeze_arc() {
+        let arc: Arc<[_]> = grow_lock!(5, [1, 2, 3]).freeze().into();
+        assert_eq!(&*arc, &[1, 2, 3]);
+        let arc: Arc<[()]> = grow_lock!(5, [(), ()]).into();
+        assert_eq!(arc.len(), 2);
+    }
+
+    #[test]
+    fn freeze_drop() {
+        struct AddOnDrop<'a>(&'a AtomicUsize);
+        impl Drop for AddOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let counter = AtomicUsize::new(0);
+        let lock = GrowLock::with_capacity(5);
+        lock.write()
+            .unwrap()
+            .extend([AddOnDrop(&counter), AddOnDrop(&counter)]);
+        let frozen = lock.freeze();
+        assert_eq!(counter.load(Ordering::Relaxed), 0);
+        drop(frozen);
+        assert_eq!(counter.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,273 @@
+//! Asynchronous wait for the elements and the write access of a
+//! [`GrowLock`].
+//!
+//! The types of this module are executor-agnostic: they register the
+//! [`Waker`](std::task::Waker) of the task on the lock, and the writers
+//! wake it up when they publish new elements or release the lock. As for
+//! [`GrowLock::wait_for_len`], writers wake the waiters once per batch,
+//! and only take a lock when someone is waiting.
+//!
+//! This module is only available with the `async` feature.
+
+use {
+    crate::{
+        GrowLock,
+        allocator::{Allocator, Global},
+        error::{LockResult, PoisonError, TryLockError},
+        guard::GrowGuard,
+        lock::{DefaultLock, WriteLock},
+    },
+    futures_core::{FusedStream, Stream},
+    std::{
+        fmt,
+        pin::Pin,
+        task::{Context, Poll, ready},
+    },
+};
+
+/// Future resolving to the length of a [`GrowLock`] once it has at least
+/// a given number of elements.
+///
+/// This structure is created by the [`wait_len`](GrowLock::wait_len)
+/// method on [`GrowLock`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WaitLen<
+    'lock,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'lock GrowLock<T, A, L>,
+    n: usize,
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock> WaitLen<'lock, T, A, L> {
+    #[inline]
+    pub(crate) const fn new(
+        lock: &'lock GrowLock<T, A, L>,
+        n: usize,
+    ) -> Self {
+        Self { lock, n }
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Future for WaitLen<'_, T, A, L> {
+    type Output = usize;
+    #[inline]
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<usize> {
+        self.lock.waiters.poll_wait(&self.lock.len, self.n, cx)
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> fmt::Debug for WaitLen<'_, T, A, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WaitLen")
+            .field("n", &self.n)
+            .finish_non_exhaustive()
+    }
+}
+
+/// [`Stream`] of the elements of a [`GrowLock`], yielding each of them
+/// as soon as it is published.
+///
+/// The stream ends once the [`GrowLock`] is full and all of its elements
+/// have been yielded.
+///
+/// This structure is created by the [`subscribe`](GrowLock::subscribe)
+/// and [`subscribe_from`](GrowLock::subscribe_from) methods on
+/// [`GrowLock`].
+#[must_use = "streams do nothing unless polled"]
+pub struct Subscriber<
+    'lock,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'lock GrowLock<T, A, L>,
+    /// Index of the next element to yield.
+    next: usize,
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock> Subscriber<'lock, T, A, L> {
+    #[inline]
+    pub(crate) const fn new(
+        lock: &'lock GrowLock<T, A, L>,
+        next: usize,
+    ) -> Self {
+        Self { lock, next }
+    }
+    /// Returns the index of the next element this [`Subscriber`] will
+    /// yield.
+    #[inline]
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.next
+    }
+    /// Polls for the next element, registering the current task to be
+    /// woken up when it is published.
+    ///
+    /// Returns `None` once every element of a full [`GrowLock`] has been
+    /// yielded.
+    pub fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<&'lock T>> {
+        if self.next >= self.lock.capacity() {
+            return Poll::Ready(None);
+        }
+        ready!(self.lock.waiters.poll_wait(
+            &self.lock.len,
+            self.next + 1,
+            cx
+        ));
+        let item = &self.lock.as_slice()[self.next];
+        self.next += 1;
+        Poll::Ready(Some(item))
+    }
+    /// Returns a future resolving to the next element, as
+    /// [`Subscriber::poll_next`].
+    #[inline]
+    pub const fn next(&mut self) -> Next<'_, 'lock, T, A, L> {
+        Next { subscriber: self }
+    }
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock> Stream
+    for Subscriber<'lock, T, A, L>
+{
+    type Item = &'lock T;
+    #[inline]
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<&'lock T>> {
+        Subscriber::poll_next(self.get_mut(), cx)
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let capacity = self.lock.capacity();
+        (
+            self.lock.len().saturating_sub(self.next),
+            Some(capacity - self.next.min(capacity)),
+        )
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> FusedStream
+    for Subscriber<'_, T, A, L>
+{
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        self.next >= self.lock.capacity()
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> fmt::Debug
+    for Subscriber<'_, T, A, L>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscriber")
+            .field("next", &self.next)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Future resolving to the next element of a [`Subscriber`].
+///
+/// This structure is created by the [`next`](Subscriber::next) method on
+/// [`Subscriber`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Next<
+    'a,
+    'lock,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    subscriber: &'a mut Subscriber<'lock, T, A, L>,
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock> Future
+    for Next<'_, 'lock, T, A, L>
+{
+    type Output = Option<&'lock T>;
+    #[inline]
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<&'lock T>> {
+        self.subscriber.poll_next(cx)
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> fmt::Debug for Next<'_, '_, T, A, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Next")
+            .field("subscriber", &self.subscriber)
+            .finish()
+    }
+}
+
+/// Future resolving to the write access of a [`GrowLock`].
+///
+/// This structure is created by the [`write_async`](GrowLock::write_async)
+/// method on [`GrowLock`].
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct WriteAsync<
+    'lock,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'lock GrowLock<T, A, L>,
+    /// Position in the queue of the asynchronous writers, if enqueued.
+    ticket: Option<u64>,
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock> WriteAsync<'lock, T, A, L> {
+    #[inline]
+    pub(crate) const fn new(lock: &'lock GrowLock<T, A, L>) -> Self {
+        Self { lock, ticket: None }
+    }
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock> Future
+    for WriteAsync<'lock, T, A, L>
+{
+    type Output = LockResult<GrowGuard<'lock, T, A, L>>;
+    fn poll(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let lock = this.lock;
+        lock.writers.poll_acquire(&mut this.ticket, cx, || {
+            match lock.try_write() {
+                Ok(guard) => Some(Ok(guard)),
+                Err(TryLockError::Poisoned(e)) => {
+                    Some(Err(PoisonError::new(e.into_inner())))
+                }
+                Err(TryLockError::WouldBlock) => None,
+            }
+        })
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Drop for WriteAsync<'_, T, A, L> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(ticket) = self.ticket {
+            self.lock.writers.cancel(ticket);
+        }
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> fmt::Debug
+    for WriteAsync<'_, T, A, L>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WriteAsync")
+            .field("ticket", &self.ticket)
+            .finish_non_exhaustive()
+    }
+}
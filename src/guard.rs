@@ -1,16 +1,217 @@
 #[cfg(not(loom))]
-use std::sync::{MutexGuard, atomic::Ordering};
+use core::sync::atomic::Ordering;
 
 #[cfg(loom)]
-use loom::sync::{MutexGuard, atomic::Ordering};
+use loom::sync::atomic::Ordering;
 use {
-    crate::{GrowLock, error::LengthError},
-    std::{
-        alloc::{Allocator, Global},
+    crate::{
+        GrowLock,
+        allocator::{Allocator, Global},
+        error::LengthError,
+        lock::{DefaultLock, WriteLock, WritePermit},
+    },
+    alloc::sync::Arc,
+    core::{
+        mem::{self, MaybeUninit},
         ops,
+        ptr::{self, NonNull},
     },
 };
 
+/// Implements the write methods shared by [`GrowGuard`] and
+/// [`OwnedGrowGuard`], whose `lock` field derefs to the [`GrowLock`].
+///
+/// The examples of [`spare_capacity_mut`](GrowGuard::spare_capacity_mut)
+/// and [`stage`](GrowGuard::stage) are given for each guard.
+macro_rules! guard_methods {
+    (
+        spare_capacity_mut: $(#[$spare:meta])*
+        stage: $(#[$stage:meta])*
+    ) => {
+        #[inline]
+        #[must_use]
+        pub fn as_slice(&self) -> &[T] {
+            self.lock.as_slice()
+        }
+        #[inline]
+        #[must_use]
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+        #[inline]
+        #[must_use]
+        pub fn is_full(&self) -> bool {
+            self.len() == self.capacity()
+        }
+        #[inline]
+        #[must_use]
+        pub fn len(&self) -> usize {
+            // SAFETY: we hold the write access of the lock.
+            unsafe { self.lock.locked_len() }
+        }
+        /// Appends `value`, publishing it to the readers right away.
+        ///
+        /// The threads waiting for a length are only woken up when the
+        /// guard is dropped, so pushing doesn't pay for the wake-up.
+        ///
+        /// # Panics
+        /// Panics if `self.is_full()`.
+        pub fn push(&mut self, value: T) {
+            // SAFETY: we hold the write access of the lock.
+            unsafe { self.lock.push_locked(value) };
+        }
+        /// Like `push`, the waiting threads are woken up when the guard is
+        /// dropped.
+        ///
+        /// # Errors
+        /// Returns an error if `self.is_full()`.
+        pub fn try_push(&mut self, value: T) -> Result<(), LengthError> {
+            // SAFETY: we hold the write access of the lock.
+            unsafe { self.lock.try_push_locked(value) }
+        }
+        /// Clones and appends all the elements of `other`, publishing them
+        /// at once.
+        ///
+        /// # Panics
+        /// Panics if `other` doesn't fit in the spare capacity. In this
+        /// case, nothing is written.
+        pub fn extend_from_slice(&mut self, other: &[T])
+        where
+            T: Clone,
+        {
+            assert!(
+                other.len() <= self.capacity() - self.len(),
+                "length overflow"
+            );
+            // SAFETY: we hold the write access of the lock, and `other`
+            // fits.
+            unsafe { self.lock.extend_from_slice_locked(other) };
+        }
+        /// Clones and appends all the elements of `other`, publishing them
+        /// at once.
+        ///
+        /// # Errors
+        /// Returns an error if `other` doesn't fit in the spare capacity.
+        /// In this case, nothing is written.
+        pub fn try_extend_from_slice(
+            &mut self,
+            other: &[T],
+        ) -> Result<(), LengthError>
+        where
+            T: Clone,
+        {
+            if other.len() > self.capacity() - self.len() {
+                return Err(LengthError);
+            }
+            // SAFETY: we hold the write access of the lock, and `other`
+            // fits.
+            unsafe { self.lock.extend_from_slice_locked(other) };
+            Ok(())
+        }
+        /// Copies all the elements of `other` with a single `memcpy`,
+        /// publishing them at once.
+        ///
+        /// # Panics
+        /// Panics if `other` doesn't fit in the spare capacity. In this
+        /// case, nothing is written.
+        pub fn extend_from_copy_slice(&mut self, other: &[T])
+        where
+            T: Copy,
+        {
+            assert!(
+                other.len() <= self.capacity() - self.len(),
+                "length overflow"
+            );
+            // SAFETY: we hold the write access of the lock, and `other`
+            // fits.
+            unsafe { self.lock.extend_from_copy_slice_locked(other) };
+        }
+        /// Copies all the elements of `other` with a single `memcpy`,
+        /// publishing them at once.
+        ///
+        /// # Errors
+        /// Returns an error if `other` doesn't fit in the spare capacity.
+        /// In this case, nothing is written.
+        pub fn try_extend_from_copy_slice(
+            &mut self,
+            other: &[T],
+        ) -> Result<(), LengthError>
+        where
+            T: Copy,
+        {
+            if other.len() > self.capacity() - self.len() {
+                return Err(LengthError);
+            }
+            // SAFETY: we hold the write access of the lock, and `other`
+            // fits.
+            unsafe { self.lock.extend_from_copy_slice_locked(other) };
+            Ok(())
+        }
+        /// Returns the spare capacity of the lock as a slice of
+        /// [`MaybeUninit<T>`].
+        ///
+        /// The returned slice can be used to fill the lock with data in
+        /// place (e.g. by reading from a file), before publishing it with
+        /// [`set_len`](Self::set_len) or [`publish`](Self::publish).
+        /// Readers never see the spare capacity.
+        $(#[$spare])*
+        #[inline]
+        pub fn spare_capacity_mut(&mut self) -> &mut [MaybeUninit<T>] {
+            // SAFETY: we hold the write access of the lock, and the slice
+            // borrows the guard.
+            unsafe { self.lock.spare_capacity_locked().as_mut() }
+        }
+        /// Sets the length of the lock to `new_len`, publishing the
+        /// elements up to it.
+        ///
+        /// # Panics
+        /// Panics if `new_len` is less than the length (as published
+        /// elements can't be removed), or greater than the capacity.
+        ///
+        /// # Safety
+        /// The elements from the old length to `new_len` must be
+        /// initialized, e.g. through
+        /// [`spare_capacity_mut`](Self::spare_capacity_mut).
+        #[inline]
+        pub unsafe fn set_len(&mut self, new_len: usize) {
+            // SAFETY: we hold the write access of the lock, and the caller
+            // ensures the elements are initialized.
+            unsafe { self.lock.set_len_locked(new_len) };
+        }
+        /// Publishes the first `n` elements of the spare capacity.
+        ///
+        /// This is the same as `self.set_len(self.len() + n)`.
+        ///
+        /// # Panics
+        /// Panics if `n` is greater than the spare capacity.
+        ///
+        /// # Safety
+        /// The first `n` elements of the spare capacity must be
+        /// initialized, e.g. through
+        /// [`spare_capacity_mut`](Self::spare_capacity_mut).
+        #[inline]
+        pub unsafe fn publish(&mut self, n: usize) {
+            assert!(n <= self.capacity() - self.len(), "length overflow");
+            // SAFETY: we hold the write access of the lock, and the caller
+            // ensures the elements are initialized.
+            unsafe { self.lock.set_len_locked(self.len() + n) };
+        }
+        /// Starts a batch of writes, which are published at once by
+        /// [`Staged::commit`].
+        ///
+        /// Until then, readers keep seeing the old length. If the batch is
+        /// dropped (or rolled back) instead, the staged elements are
+        /// dropped.
+        $(#[$stage])*
+        #[inline]
+        pub fn stage(&mut self) -> Staged<'_, T, A, L> {
+            // SAFETY: we hold the write access of the lock, and the batch
+            // borrows the guard.
+            unsafe { Staged::new(&self.lock) }
+        }
+    };
+}
+
 /// RAII structure used to release the exclusive write access of a lock
 /// when dropped.
 ///
@@ -19,106 +220,578 @@ use {
 ///
 /// [write]: GrowLock::write
 /// [try_write]: GrowLock::try_write
-pub struct GrowGuard<'lock, T, A: Allocator = Global> {
-    lock: &'lock GrowLock<T, A>,
-    _guard: MutexGuard<'lock, ()>,
+pub struct GrowGuard<
+    'lock,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'lock GrowLock<T, A, L>,
+    /// Released in [`Drop`], before waking the other writers up.
+    permit: WritePermit,
 }
 
-impl<T, A: Allocator> ops::Deref for GrowGuard<'_, T, A> {
+impl<T, A: Allocator, L: WriteLock> ops::Deref for GrowGuard<'_, T, A, L> {
     type Target = [T];
     #[inline]
     fn deref(&self) -> &Self::Target {
         self.as_slice()
     }
 }
-impl<'lock, T, A: Allocator> GrowGuard<'lock, T, A> {
+impl<'lock, T, A: Allocator, L: WriteLock> GrowGuard<'lock, T, A, L> {
+    /// Creates a new guard, waiting for the in-flight lock-free producers
+    /// to finish.
     #[inline]
     #[must_use]
-    pub(super) const fn new(
-        lock: &'lock GrowLock<T, A>,
-        guard: MutexGuard<'lock, ()>,
+    pub(super) fn new(
+        lock: &'lock GrowLock<T, A, L>,
+        permit: WritePermit,
     ) -> Self {
-        Self {
-            lock,
-            _guard: guard,
-        }
+        lock.producers.lock_writer();
+        Self { lock, permit }
     }
     #[inline]
     #[must_use]
-    pub fn as_slice(&self) -> &[T] {
-        self.lock.as_slice()
+    pub const fn capacity(&self) -> usize {
+        self.lock.capacity()
+    }
+    guard_methods! {
+        spare_capacity_mut:
+        /// # Examples
+        /// ```
+        /// use growlock::GrowLock;
+        ///
+        /// let lock = GrowLock::with_capacity(8);
+        /// let mut guard = lock.write().unwrap();
+        /// let spare = guard.spare_capacity_mut();
+        /// for (i, slot) in spare[..3].iter_mut().enumerate() {
+        ///     slot.write([i as u8; 4096]);
+        /// }
+        /// // SAFETY: the first 3 elements are initialized.
+        /// unsafe { guard.publish(3) };
+        /// drop(guard);
+        /// assert_eq!(lock[2], [2; 4096]);
+        /// ```
+        stage:
+        /// # Examples
+        /// ```
+        /// use growlock::GrowLock;
+        ///
+        /// let lock = GrowLock::with_capacity(5);
+        /// let mut guard = lock.write().unwrap();
+        /// let mut batch = guard.stage();
+        /// batch.push(1);
+        /// batch.push(2);
+        /// assert!(lock.is_empty());
+        /// batch.commit();
+        /// assert_eq!(&lock[..], &[1, 2]);
+        /// ```
     }
+    /// Appends an element, returning its index and a reference to it.
+    ///
+    /// The reference borrows the [`GrowLock`], not the guard: published
+    /// elements are never mutated nor removed, so it stays valid after
+    /// further pushes and after the guard is dropped.
+    ///
+    /// # Panics
+    /// Panics if `self.is_full()`.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// let mut guard = lock.write().unwrap();
+    /// let (index, first) = guard.push_get(String::from("first"));
+    /// guard.push(String::from("second"));
+    /// drop(guard);
+    /// assert_eq!(index, 0);
+    /// assert_eq!(first, "first");
+    /// ```
+    pub fn push_get(&mut self, value: T) -> (usize, &'lock T) {
+        let index = self.len();
+        self.push(value);
+        (index, &self.lock.as_slice()[index])
+    }
+    /// Appends an element, returning its index and a reference to it, as
+    /// [`push_get`](GrowGuard::push_get).
+    ///
+    /// # Errors
+    /// Returns an error if `self.is_full()`.
+    pub fn try_push_get(
+        &mut self,
+        value: T,
+    ) -> Result<(usize, &'lock T), LengthError> {
+        let index = self.len();
+        self.try_push(value)?;
+        Ok((index, &self.lock.as_slice()[index]))
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Drop for GrowGuard<'_, T, A, L> {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the permit comes from the lock, and it is never used
+        // again.
+        unsafe { self.lock.release_writer(&self.permit) };
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Extend<T> for GrowGuard<'_, T, A, L> {
+    /// Extends the [`GrowLock<T>`] with the contents of an iterator.
+    ///
+    /// # Panics
+    /// This panics if the iterator has more elements than
+    /// `self.capacity() - self.len()` (i.e. pushing all the
+    /// elements would overflow `self.capacity()`.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        // SAFETY: we hold the write access of the lock.
+        unsafe { self.lock.extend_locked(iter) };
+    }
+}
+
+/// RAII structure used to release the exclusive write access of a lock
+/// when dropped, keeping the lock alive with an [`Arc`].
+///
+/// Unlike [`GrowGuard`], this doesn't borrow the lock, so it can be
+/// stored in a struct.
+///
+/// It has the same write methods as [`GrowGuard`], except
+/// [`push_get`](GrowGuard::push_get): there is no lifetime of the lock
+/// for the returned reference to borrow. The pushed element can be
+/// reached by its index through [`OwnedGrowGuard::lock`] instead, which
+/// stays valid as long as the [`Arc`] is kept.
+///
+/// This structure is created by the [`write_arc`][write_arc] and
+/// [`try_write_arc`][try_write_arc] method on [`GrowLock`]
+///
+/// [write_arc]: GrowLock::write_arc
+/// [try_write_arc]: GrowLock::try_write_arc
+pub struct OwnedGrowGuard<
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: Arc<GrowLock<T, A, L>>,
+    /// Released in [`Drop`], before waking the other writers up.
+    permit: WritePermit,
+}
+
+impl<T, A: Allocator, L: WriteLock> ops::Deref
+    for OwnedGrowGuard<T, A, L>
+{
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+impl<T, A: Allocator, L: WriteLock> OwnedGrowGuard<T, A, L> {
+    /// Creates a new guard, waiting for the in-flight lock-free producers
+    /// to finish.
     #[inline]
     #[must_use]
-    pub fn is_empty(&self) -> bool {
-        self.len() == 0
+    pub(super) fn new(
+        lock: Arc<GrowLock<T, A, L>>,
+        permit: WritePermit,
+    ) -> Self {
+        lock.producers.lock_writer();
+        Self { lock, permit }
     }
+    /// Returns the [`GrowLock`] locked by this guard.
     #[inline]
     #[must_use]
-    pub fn is_full(&self) -> bool {
-        self.len() == self.capacity()
+    pub fn lock(this: &Self) -> &Arc<GrowLock<T, A, L>> {
+        &this.lock
     }
     #[inline]
     #[must_use]
-    pub const fn capacity(&self) -> usize {
+    pub fn capacity(&self) -> usize {
         self.lock.capacity()
     }
+    guard_methods! {
+        spare_capacity_mut:
+        /// # Examples
+        /// ```
+        /// use growlock::GrowLock;
+        /// use std::sync::Arc;
+        ///
+        /// let lock = Arc::new(GrowLock::with_capacity(8));
+        /// let mut guard = lock.write_arc().unwrap();
+        /// guard.spare_capacity_mut()[0].write(42);
+        /// // SAFETY: the first element is initialized.
+        /// unsafe { guard.set_len(1) };
+        /// assert_eq!(&lock[..], &[42]);
+        /// ```
+        stage:
+        /// # Examples
+        /// ```
+        /// use growlock::GrowLock;
+        /// use std::sync::Arc;
+        ///
+        /// let lock = Arc::new(GrowLock::with_capacity(5));
+        /// let mut guard = lock.write_arc().unwrap();
+        /// let mut batch = guard.stage();
+        /// batch.extend([1, 2]);
+        /// batch.rollback();
+        /// assert!(lock.is_empty());
+        /// ```
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Drop for OwnedGrowGuard<T, A, L> {
     #[inline]
-    #[must_use]
-    pub fn len(&self) -> usize {
-        // We locked the mutex so writes cannot happen.
-        self.lock.len.load(Ordering::Relaxed)
+    fn drop(&mut self) {
+        // SAFETY: the permit comes from the lock, and it is never used
+        // again.
+        unsafe { self.lock.release_writer(&self.permit) };
     }
+}
+
+impl<T, A: Allocator, L: WriteLock> Extend<T> for OwnedGrowGuard<T, A, L> {
+    /// Extends the [`GrowLock<T>`] with the contents of an iterator.
+    ///
     /// # Panics
-    /// Panics if `self.is_full()`.
-    pub fn push(&mut self, value: T) {
-        let len = self.len();
-        let cap = self.capacity();
+    /// This panics if the iterator has more elements than
+    /// `self.capacity() - self.len()` (i.e. pushing all the
+    /// elements would overflow `self.capacity()`.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        // SAFETY: we hold the write access of the lock.
+        unsafe { self.lock.extend_locked(iter) };
+    }
+}
 
-        assert!(len < cap, "length overflow");
+/// Batch of writes to a [`GrowLock`], which become visible to the readers
+/// at once when committed.
+///
+/// The staged elements are written in the spare capacity of the lock,
+/// past its length. If the batch is dropped without being committed (for
+/// example on a panic), the staged elements are dropped and the readers
+/// never see them.
+///
+/// This structure is created by the [`stage`](GrowGuard::stage) method on
+/// [`GrowGuard`] and [`OwnedGrowGuard`].
+#[must_use = "the staged elements are dropped unless committed"]
+pub struct Staged<
+    'guard,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'guard GrowLock<T, A, L>,
+    /// Length of the lock when the batch was started.
+    base: usize,
+    elements: Unpublished<T>,
+}
 
-        // SAFETY: the ptr is still in the allocated block, even after
-        // add(len)
-        unsafe {
-            let dst = self.lock.as_non_null_ref().add(len);
-            dst.write(value);
-            self.lock.len.store(len + 1, Ordering::Relaxed);
+impl<'guard, T, A: Allocator, L: WriteLock> Staged<'guard, T, A, L> {
+    /// # Safety
+    /// The caller must hold the write access of the lock for `'guard`.
+    #[inline]
+    unsafe fn new(lock: &'guard GrowLock<T, A, L>) -> Self {
+        // SAFETY: the caller holds the write access of the lock.
+        let base = unsafe { lock.locked_len() };
+        Self {
+            lock,
+            base,
+            // SAFETY: the spare capacity starts at `base`.
+            elements: Unpublished::new(unsafe {
+                lock.as_non_null_ref().add(base)
+            }),
         }
     }
+    /// Returns the staged elements.
+    #[inline]
+    #[must_use]
+    pub const fn as_slice(&self) -> &[T] {
+        self.elements.as_slice()
+    }
+    /// Returns the number of staged elements.
+    #[inline]
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.elements.len
+    }
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the number of elements which can still be staged.
+    #[inline]
+    #[must_use]
+    pub const fn remaining(&self) -> usize {
+        self.lock.capacity() - self.base - self.len()
+    }
+    /// Stages an element.
+    ///
+    /// # Panics
+    /// Panics if `self.remaining() == 0`.
+    pub fn push(&mut self, value: T) {
+        assert!(self.remaining() > 0, "length overflow");
+        // SAFETY: there's room for `value` in the spare capacity.
+        unsafe { self.elements.push(value) };
+    }
+    /// Stages an element.
+    ///
     /// # Errors
-    /// Returns an error if `self.is_full()`.
+    /// Returns an error if `self.remaining() == 0`.
     pub fn try_push(&mut self, value: T) -> Result<(), LengthError> {
-        // We locked the mutex so writes cannot happen.
-        let len = self.lock.len.load(Ordering::Relaxed);
-        let cap = self.lock.capacity();
-
-        if len >= cap {
+        if self.remaining() == 0 {
             return Err(LengthError);
         }
-
-        // SAFETY: the ptr is still in the allocated block, even after
-        // add(len)
-        unsafe {
-            let dst = self.lock.as_non_null_ref().add(len);
-            dst.write(value);
-        }
-        self.lock.len.store(len + 1, Ordering::Relaxed);
-
+        // SAFETY: there's room for `value` in the spare capacity.
+        unsafe { self.elements.push(value) };
         Ok(())
     }
+    /// Publishes all the staged elements with a single store.
+    pub fn commit(self) {
+        let Self {
+            lock,
+            base,
+            elements,
+        } = self;
+        let len = base + elements.len;
+        elements.publish();
+        // Release: publishes the staged elements to the readers.
+        lock.len.store(len, Ordering::Release);
+        lock.waiters.notify();
+    }
+    /// Drops all the staged elements, as dropping `self`.
+    #[inline]
+    pub fn rollback(self) {}
 }
 
-impl<T, A: Allocator> Extend<T> for GrowGuard<'_, T, A> {
-    /// Extends the [`GrowLock<T>`] with the contents of an iterator.
+impl<T, A: Allocator, L: WriteLock> Extend<T> for Staged<'_, T, A, L> {
+    /// Stages the contents of an iterator.
     ///
     /// # Panics
     /// This panics if the iterator has more elements than
-    /// `self.capacity() - self.len()` (i.e. pushing all the
-    /// elements would overflow `self.capacity()`.
+    /// `self.remaining()`.
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        let iter = iter.into_iter();
         for elem in iter {
             self.push(elem);
         }
     }
 }
+
+/// Write operations shared by [`GrowGuard`] and [`OwnedGrowGuard`].
+///
+/// # Safety
+/// All of these must be called while holding the write access of the lock
+/// (i.e. from a guard).
+impl<T, A: Allocator, L: WriteLock> GrowLock<T, A, L> {
+    #[inline]
+    pub(crate) unsafe fn locked_len(&self) -> usize {
+        // We hold the write lock so writes cannot happen, and the only
+        // thread that stores `len` is this one.
+        self.len.load(Ordering::Relaxed)
+    }
+    /// Appends an element, without waking up the threads waiting for the
+    /// length of the lock.
+    ///
+    /// # Panics
+    /// Panics if the lock is full.
+    pub(crate) unsafe fn push_locked(&self, value: T) {
+        // SAFETY: the caller holds the write access of the lock.
+        let len = unsafe { self.locked_len() };
+        assert!(len < self.capacity(), "length overflow");
+        // SAFETY: `len` is less than the capacity.
+        unsafe { self.append_locked(len, value) };
+    }
+    /// Appends an element if the lock isn't full, without waking up the
+    /// threads waiting for the length of the lock.
+    pub(crate) unsafe fn try_push_locked(
+        &self,
+        value: T,
+    ) -> Result<(), LengthError> {
+        // SAFETY: the caller holds the write access of the lock.
+        let len = unsafe { self.locked_len() };
+        if len >= self.capacity() {
+            return Err(LengthError);
+        }
+        // SAFETY: `len` is less than the capacity.
+        unsafe { self.append_locked(len, value) };
+        Ok(())
+    }
+    /// Writes `value` at index `len` and publishes it.
+    ///
+    /// # Safety
+    /// `len` must be the length of the lock, and be less than its
+    /// capacity.
+    unsafe fn append_locked(&self, len: usize, value: T) {
+        // SAFETY: the ptr is still in the allocated block, even after
+        // add(len)
+        unsafe {
+            let dst = self.as_non_null_ref().add(len);
+            dst.write(value);
+        }
+        // Release: publishes the element written above to the readers.
+        self.len.store(len + 1, Ordering::Release);
+    }
+    /// Clones and appends the elements of `other`, publishing them with a
+    /// single store.
+    ///
+    /// If a clone panics, the elements cloned so far are dropped and the
+    /// length is left untouched.
+    ///
+    /// # Safety
+    /// `other` must fit in the spare capacity.
+    pub(crate) unsafe fn extend_from_slice_locked(&self, other: &[T])
+    where
+        T: Clone,
+    {
+        // SAFETY: the caller holds the write access of the lock.
+        let len = unsafe { self.locked_len() };
+        // SAFETY: the spare capacity starts at `len`.
+        let mut written =
+            Unpublished::new(unsafe { self.as_non_null_ref().add(len) });
+        for elem in other {
+            // SAFETY: `other` fits in the spare capacity.
+            unsafe { written.push(elem.clone()) };
+        }
+        written.publish();
+        // Release: publishes the elements written above to the readers.
+        self.len.store(len + other.len(), Ordering::Release);
+        self.waiters.notify();
+    }
+    /// Copies the elements of `other`, publishing them with a single
+    /// store.
+    ///
+    /// # Safety
+    /// `other` must fit in the spare capacity.
+    pub(crate) unsafe fn extend_from_copy_slice_locked(&self, other: &[T])
+    where
+        T: Copy,
+    {
+        // SAFETY: the caller holds the write access of the lock.
+        let len = unsafe { self.locked_len() };
+        // SAFETY: `other` fits in the spare capacity, which starts at
+        // `len`, and it can't overlap with it as it's not initialized.
+        unsafe {
+            self.as_non_null_ref().add(len).copy_from_nonoverlapping(
+                NonNull::from(other).cast(),
+                other.len(),
+            );
+        }
+        // Release: publishes the elements written above to the readers.
+        self.len.store(len + other.len(), Ordering::Release);
+        self.waiters.notify();
+    }
+    /// Returns the spare capacity of the lock.
+    pub(crate) unsafe fn spare_capacity_locked(
+        &self,
+    ) -> NonNull<[MaybeUninit<T>]> {
+        // SAFETY: the caller holds the write access of the lock.
+        let len = unsafe { self.locked_len() };
+        NonNull::slice_from_raw_parts(
+            // SAFETY: the spare capacity starts at `len`.
+            unsafe { self.as_non_null_ref().add(len).cast() },
+            self.capacity() - len,
+        )
+    }
+    /// Publishes the elements up to `new_len`.
+    ///
+    /// # Panics
+    /// Panics if `new_len` is not between the length and the capacity.
+    ///
+    /// # Safety
+    /// The elements up to `new_len` must be initialized.
+    pub(crate) unsafe fn set_len_locked(&self, new_len: usize) {
+        // SAFETY: the caller holds the write access of the lock.
+        let len = unsafe { self.locked_len() };
+        assert!(
+            new_len >= len,
+            "cannot shrink the length from {len} to {new_len}"
+        );
+        assert!(new_len <= self.capacity(), "length overflow");
+        // Release: publishes the elements initialized by the caller.
+        self.len.store(new_len, Ordering::Release);
+        self.waiters.notify();
+    }
+    /// Appends the elements of `iter`, waking up the threads waiting for
+    /// the length of the lock once at the end.
+    ///
+    /// # Panics
+    /// Panics if the lock becomes full.
+    pub(crate) unsafe fn extend_locked<I: IntoIterator<Item = T>>(
+        &self,
+        iter: I,
+    ) {
+        for elem in iter {
+            // SAFETY: the caller holds the write access of the lock.
+            unsafe { self.push_locked(elem) };
+        }
+        self.waiters.notify();
+    }
+    /// Releases the write access of the lock held by `permit`, and wakes
+    /// up the waiting threads.
+    ///
+    /// # Safety
+    /// `permit` must come from this lock, and it must not be used after
+    /// this call.
+    pub(crate) unsafe fn release_writer(&self, permit: &WritePermit) {
+        // lets the lock-free producers in again (before the write lock is
+        // released).
+        // SAFETY: the caller holds the write access of the lock.
+        self.producers.unlock_writer(unsafe { self.locked_len() });
+        // SAFETY: the caller doesn't use the permit anymore. It must be
+        // released before waking up the next asynchronous writer, or its
+        // `try_lock` could fail.
+        unsafe { self.write_lock.unlock(permit) };
+        #[cfg(feature = "async")]
+        self.writers.notify();
+        // this also covers the elements published by an `extend` which
+        // panicked.
+        self.waiters.notify();
+    }
+}
+
+/// Elements written past the length of a lock, which are dropped unless
+/// they are published.
+struct Unpublished<T> {
+    start: NonNull<T>,
+    len: usize,
+}
+
+impl<T> Unpublished<T> {
+    #[inline]
+    const fn new(start: NonNull<T>) -> Self {
+        Self { start, len: 0 }
+    }
+    /// Writes `value` after the elements written so far.
+    ///
+    /// # Safety
+    /// There must be room for `value` in the spare capacity.
+    #[inline]
+    unsafe fn push(&mut self, value: T) {
+        // SAFETY: the caller ensures there's room for `value`.
+        unsafe { self.start.add(self.len).write(value) };
+        self.len += 1;
+    }
+    #[inline]
+    const fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements were written.
+        unsafe {
+            NonNull::slice_from_raw_parts(self.start, self.len).as_ref()
+        }
+    }
+    /// Leaves the written elements to the lock, which must publish them.
+    #[inline]
+    fn publish(self) {
+        mem::forget(self);
+    }
+}
+
+impl<T> Drop for Unpublished<T> {
+    fn drop(&mut self) {
+        // SAFETY: the first `len` elements were written, and they were
+        // never published.
+        unsafe {
+            ptr::drop_in_place(
+                NonNull::slice_from_raw_parts(self.start, self.len)
+                    .as_ptr(),
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Open-addressing hash index over the elements of a
+//! [`GrowLock`](crate::GrowLock).
+//!
+//! The index maps hashes to the ids (i.e. the indices in the lock) of the
+//! elements, with linear probing. It never grows nor removes an entry, so
+//! readers can probe it without locking:
+//! * a writer first publishes the element in the lock, and then stores its
+//!   id in a free slot of the index with [`Ordering::Release`].
+//! * a reader loads the slots with [`Ordering::Acquire`], so the elements
+//!   they point to are visible.
+//!
+//! Writers must be serialized by the caller (e.g. by the write lock of the
+//! [`GrowLock`](crate::GrowLock)). [`IndexedLock`] does so, and inserts
+//! each element at most once.
+
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+use {
+    crate::{GrowLock, error::LengthError},
+    std::{ops, sync::PoisonError},
+};
+
+/// Hash index of at most `capacity` elements.
+pub(crate) struct Index {
+    /// `id + 1` of the element in each slot, or `0` if the slot is empty.
+    ///
+    /// There are at least twice as many slots as elements, so probes are
+    /// short and a free slot always exists.
+    slots: Box<[AtomicUsize]>,
+}
+
+impl Index {
+    /// Creates an empty index for at most `capacity` elements.
+    ///
+    /// # Panics
+    /// Panics if the number of slots overflows a `usize`.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let slots = capacity
+            .checked_mul(2)
+            .and_then(usize::checked_next_power_of_two)
+            .expect("capacity overflow")
+            .max(1);
+        Self {
+            slots: (0..slots).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    /// Returns the id of the first element with the given `hash` for which
+    /// `is_match` returns `true`.
+    pub(crate) fn find(
+        &self,
+        hash: u64,
+        mut is_match: impl FnMut(usize) -> bool,
+    ) -> Option<usize> {
+        self.probe(hash)
+            .map(|slot| slot.load(Ordering::Acquire))
+            .take_while(|&entry| entry != 0)
+            .map(|entry| entry - 1)
+            .find(|&id| is_match(id))
+    }
+
+    /// Inserts the element `id` with the given `hash`.
+    ///
+    /// # Safety
+    /// * the element `id` must be published before this call.
+    /// * the index must hold less than `capacity` elements.
+    /// * writers must not call this concurrently.
+    pub(crate) unsafe fn insert(&self, hash: u64, id: usize) {
+        // writers are serialized, so they see all the previous inserts.
+        let slot = self
+            .probe(hash)
+            .find(|slot| slot.load(Ordering::Relaxed) == 0);
+        // SAFETY: there are more slots than elements.
+        let slot = unsafe { slot.unwrap_unchecked() };
+        // Release: pairs with the Acquire of `find`.
+        slot.store(id + 1, Ordering::Release);
+    }
+
+    /// Returns the slots in probing order for the given `hash`.
+    fn probe(&self, hash: u64) -> impl Iterator<Item = &AtomicUsize> {
+        let mask = self.slots.len() - 1;
+        // truncating the hash is fine, only its low bits are used.
+        #[allow(clippy::cast_possible_truncation)]
+        let start = hash as usize & mask;
+        self.slots[start..].iter().chain(&self.slots[..start])
+    }
+}
+
+/// A [`GrowLock`] indexed by hash, holding at most one element per key.
+pub(crate) struct IndexedLock<T> {
+    elements: GrowLock<T>,
+    index: Index,
+    /// Maximum number of elements. This is not the capacity of
+    /// `elements`, which is `usize::MAX` for ZSTs.
+    capacity: usize,
+}
+
+impl<T> IndexedLock<T> {
+    /// Creates an empty lock which can hold up to `capacity` elements.
+    ///
+    /// # Panics
+    /// Panics if the capacity exceeds `isize::MAX` _bytes_.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            elements: GrowLock::with_capacity(capacity),
+            index: Index::with_capacity(capacity),
+            capacity,
+        }
+    }
+    /// Returns the maximum number of elements.
+    pub(crate) const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the id of the first element with the given `hash` for which
+    /// `is_match` returns `true`.
+    pub(crate) fn find(
+        &self,
+        hash: u64,
+        mut is_match: impl FnMut(&T) -> bool,
+    ) -> Option<usize> {
+        self.index.find(hash, |id| is_match(&self.elements[id]))
+    }
+
+    /// Returns the id of the element matching `key`, pushing `into(key)`
+    /// if there's none.
+    ///
+    /// # Errors
+    /// Returns an error if no element matches and the lock is full.
+    pub(crate) fn get_or_push<K>(
+        &self,
+        hash: u64,
+        key: K,
+        is_match: impl Fn(&T, &K) -> bool,
+        into: impl FnOnce(K) -> T,
+    ) -> Result<usize, LengthError> {
+        if let Some(id) = self.find(hash, |elem| is_match(elem, &key)) {
+            return Ok(id);
+        }
+
+        // the index is only updated after a successful push, so a panic
+        // (e.g. in `into` or in `is_match`) never leaves it inconsistent.
+        let mut guard = self
+            .elements
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        // another writer may have pushed it meanwhile.
+        if let Some(id) = self.find(hash, |elem| is_match(elem, &key)) {
+            return Ok(id);
+        }
+        let id = guard.len();
+        if id >= self.capacity {
+            return Err(LengthError);
+        }
+        guard.push(into(key));
+        // SAFETY: the element is published, there are less than `capacity`
+        // elements, and we hold the write lock.
+        unsafe { self.index.insert(hash, id) };
+        Ok(id)
+    }
+}
+
+impl<T> ops::Deref for IndexedLock<T> {
+    type Target = GrowLock<T>;
+    #[inline]
+    fn deref(&self) -> &GrowLock<T> {
+        &self.elements
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use {
+        super::*,
+        std::{sync::Arc, thread},
+    };
+
+    #[test]
+    fn probing() {
+        let index = Index::with_capacity(3);
+        assert_eq!(index.slots.len(), 8);
+        assert_eq!(index.find(5, |_| true), None);
+
+        // SAFETY: the index holds at most 3 elements.
+        unsafe {
+            index.insert(7, 0);
+            index.insert(7, 1);
+            index.insert(0, 2);
+        }
+        assert_eq!(index.find(7, |_| true), Some(0));
+        assert_eq!(index.find(15, |_| true), Some(0));
+        // the probe for 7 wraps around to the first slot.
+        assert_eq!(index.find(7, |id| id == 1), Some(1));
+        assert_eq!(index.find(0, |_| true), Some(1));
+        assert_eq!(index.find(0, |id| id == 2), Some(2));
+        // the probe stops at the first empty slot.
+        assert_eq!(index.find(0, |id| id == 0), None);
+        assert_eq!(index.find(2, |_| true), None);
+    }
+
+    /// Concurrent writers agree on the id of each key
+    #[test]
+    fn get_or_push_concurrent() {
+        const THREADS: usize = 8;
+        const KEYS: usize = 100;
+
+        let lock = Arc::new(IndexedLock::with_capacity(KEYS));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    (0..KEYS)
+                        .map(|i| (t + i) % KEYS)
+                        .map(|key| {
+                            let hash = key as u64;
+                            let id = lock.get_or_push(
+                                hash,
+                                key,
+                                |&(k, _), &key| k == key,
+                                |key| (key, t),
+                            );
+                            (key, id.unwrap())
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            for (key, id) in handle.join().unwrap() {
+                assert_eq!(lock[id].0, key);
+            }
+        }
+        assert_eq!(lock.len(), KEYS);
+        assert_eq!(
+            lock.get_or_push(0, KEYS, |_, _| false, |_| (0, 0)),
+            Err(LengthError)
+        );
+    }
+}
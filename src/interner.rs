@@ -0,0 +1,238 @@
+//! An append-only interner built on a [`GrowLock`].
+//!
+//! [`GrowInterner`] stores each distinct value once in a [`GrowLock`], and
+//! identifies it by its index in the lock (its *id*). An open-addressing
+//! hash index maps the values to their ids: like the [`GrowLock`] itself,
+//! it only grows, so readers can look values up and resolve ids without
+//! locking. Only interning a new value takes the write lock.
+//!
+//! [`GrowLock`]: crate::GrowLock
+
+use {
+    crate::{error::LengthError, index::IndexedLock},
+    std::{
+        borrow::Borrow,
+        fmt,
+        hash::{BuildHasher, Hash, RandomState},
+        ops, slice,
+    },
+};
+
+/// An append-only interner, mapping distinct values to consecutive ids.
+///
+/// Values are never removed: an id stays valid (and resolves to the same
+/// value) as long as the interner lives.
+///
+/// # Examples
+/// ```
+/// use growlock::interner::GrowInterner;
+///
+/// let interner = GrowInterner::with_capacity(10);
+/// let foo = interner.intern_ref("foo");
+/// let bar = interner.intern(String::from("bar"));
+///
+/// assert_eq!(interner.intern_ref("foo"), foo);
+/// assert_eq!(interner.get("bar"), Some(bar));
+/// assert_eq!(interner.resolve(foo).map(String::as_str), Some("foo"));
+/// assert_eq!(interner.len(), 2);
+/// ```
+pub struct GrowInterner<T, S = RandomState> {
+    values: IndexedLock<T>,
+    hasher: S,
+}
+
+impl<T> GrowInterner<T> {
+    /// Creates an empty [`GrowInterner`] which can hold up to `capacity`
+    /// distinct values.
+    ///
+    /// # Panics
+    /// Panics if the capacity exceeds `isize::MAX` _bytes_.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<T, S> GrowInterner<T, S> {
+    /// Creates an empty [`GrowInterner`] which can hold up to `capacity`
+    /// distinct values, using `hasher` to hash them.
+    ///
+    /// # Panics
+    /// Panics if the capacity exceeds `isize::MAX` _bytes_.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            values: IndexedLock::with_capacity(capacity),
+            hasher,
+        }
+    }
+    /// Returns the number of interned values.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the maximum number of distinct values.
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn hasher(&self) -> &S {
+        &self.hasher
+    }
+    /// Returns the value with the given id, if any.
+    #[inline]
+    #[must_use]
+    pub fn resolve(&self, id: usize) -> Option<&T> {
+        self.values.get(id)
+    }
+    /// Returns the interned values, ordered by id.
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self.values.as_slice()
+    }
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+}
+
+impl<T: Hash + Eq, S: BuildHasher> GrowInterner<T, S> {
+    /// Returns the id of `value`, if it is interned.
+    #[inline]
+    pub fn get<Q>(&self, value: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(self.hasher.hash_one(value), value)
+    }
+    /// Returns the id of `value`, interning it if needed.
+    ///
+    /// # Panics
+    /// Panics if `value` is not interned and the interner is full.
+    #[inline]
+    pub fn intern(&self, value: T) -> usize {
+        self.try_intern(value).expect("interner is full")
+    }
+    /// Returns the id of `value`, interning it if needed.
+    ///
+    /// # Errors
+    /// Returns an error if `value` is not interned and the interner is
+    /// full.
+    #[inline]
+    pub fn try_intern(&self, value: T) -> Result<usize, LengthError> {
+        self.try_intern_with::<T, T>(value, |value| value)
+    }
+    /// Returns the id of `value`, interning an owned copy of it if needed.
+    ///
+    /// Unlike [`GrowInterner::intern`], this only allocates the owned
+    /// value when `value` is not interned yet.
+    ///
+    /// # Panics
+    /// Panics if `value` is not interned and the interner is full.
+    #[inline]
+    pub fn intern_ref<Q>(&self, value: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = T> + ?Sized,
+    {
+        self.try_intern_with(value, Q::to_owned)
+            .expect("interner is full")
+    }
+
+    /// Returns the id of `value`, interning `into(value)` if needed.
+    fn try_intern_with<Q, V>(
+        &self,
+        value: V,
+        into: impl FnOnce(V) -> T,
+    ) -> Result<usize, LengthError>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Borrow<Q>,
+    {
+        let hash = self.hasher.hash_one(value.borrow());
+        self.values.get_or_push(
+            hash,
+            value,
+            |elem, value| elem.borrow() == value.borrow(),
+            into,
+        )
+    }
+    fn find<Q>(&self, hash: u64, value: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.values.find(hash, |elem| elem.borrow() == value)
+    }
+}
+
+impl<T, S> ops::Index<usize> for GrowInterner<T, S> {
+    type Output = T;
+    /// Returns the value with the given id.
+    ///
+    /// # Panics
+    /// Panics if no value has this id.
+    #[inline]
+    fn index(&self, id: usize) -> &T {
+        &self.values[id]
+    }
+}
+
+impl<'a, T, S> IntoIterator for &'a GrowInterner<T, S> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T: fmt::Debug, S> fmt::Debug for GrowInterner<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern() {
+        let interner = GrowInterner::with_capacity(3);
+        assert_eq!(interner.intern_ref("a"), 0);
+        assert_eq!(interner.intern(String::from("b")), 1);
+        assert_eq!(interner.intern_ref("a"), 0);
+        assert_eq!(interner.get("b"), Some(1));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.try_intern(String::from("c")), Ok(2));
+        assert_eq!(
+            interner.try_intern(String::from("d")),
+            Err(LengthError)
+        );
+        assert_eq!(interner.try_intern(String::from("a")), Ok(0));
+        assert_eq!(interner.as_slice(), &["a", "b", "c"]);
+    }
+
+    #[test]
+    fn intern_zst() {
+        let interner = GrowInterner::with_capacity(1);
+        assert_eq!(interner.intern(()), 0);
+        assert_eq!(interner.intern(()), 0);
+        assert_eq!(interner.len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! Iterators over a [`GrowLock`].
+
+use {
+    crate::{
+        GrowLock,
+        allocator::{Allocator, Global},
+        cap::Cap,
+        lock::WriteLock,
+        raw::RawGrowLock,
+    },
+    core::{fmt, iter::FusedIterator, ptr, slice},
+};
+
+/// An iterator that moves the elements out of a [`GrowLock`].
+///
+/// This structure is created by the `into_iter` method on [`GrowLock`]
+/// (provided by the [`IntoIterator`] trait).
+///
+/// # Examples
+/// ```
+/// use growlock::grow_lock;
+///
+/// let lock = grow_lock!(5, [String::from("a"), String::from("b")]);
+/// let mut iter = lock.into_iter();
+/// assert_eq!(iter.next().as_deref(), Some("a"));
+/// assert_eq!(iter.as_slice(), &["b"]);
+/// ```
+pub struct IntoIter<T, A: Allocator = Global> {
+    buf: RawGrowLock<T, A>,
+    /// Index of the next element yielded from the front.
+    start: usize,
+    /// Index past the next element yielded from the back.
+    end: usize,
+}
+
+// SAFETY: an `IntoIter` owns its elements and its allocator, like a
+// `vec::IntoIter<T, A>`.
+unsafe impl<T: Send, A: Allocator + Send> Send for IntoIter<T, A> {}
+// SAFETY: an `IntoIter` only gives shared access to its elements through
+// `&self`.
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for IntoIter<T, A> {}
+
+impl<T, A: Allocator> IntoIter<T, A> {
+    /// Returns the remaining elements as a slice.
+    #[inline]
+    #[must_use]
+    pub const fn as_slice(&self) -> &[T] {
+        // SAFETY: the elements in `start..end` are initialized and not
+        // yielded yet.
+        unsafe {
+            slice::from_raw_parts(
+                self.buf.as_ptr().wrapping_add(self.start),
+                self.end - self.start,
+            )
+        }
+    }
+    #[inline]
+    #[must_use]
+    pub const fn allocator(&self) -> &A {
+        self.buf.allocator()
+    }
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        // SAFETY: the element at `start` is initialized, and it is never
+        // read again.
+        let elem = unsafe { self.buf.as_ptr().add(self.start).read() };
+        self.start += 1;
+        Some(elem)
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.start;
+        (len, Some(len))
+    }
+}
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    #[inline]
+    fn next_back(&mut self) -> Option<T> {
+        if self.start == self.end {
+            return None;
+        }
+        self.end -= 1;
+        // SAFETY: the element at `end` is initialized, and it is never
+        // read again.
+        Some(unsafe { self.buf.as_ptr().add(self.end).read() })
+    }
+}
+impl<T, A: Allocator> ExactSizeIterator for IntoIter<T, A> {}
+impl<T, A: Allocator> FusedIterator for IntoIter<T, A> {}
+
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
+    fn drop(&mut self) {
+        // SAFETY: the elements in `start..end` are initialized and not
+        // yielded, so they are dropped here. The buffer is freed by
+        // `RawGrowLock`.
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                self.buf.as_mut_ptr().wrapping_add(self.start),
+                self.end - self.start,
+            ));
+        }
+    }
+}
+
+impl<T: fmt::Debug, A: Allocator> fmt::Debug for IntoIter<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IntoIter").field(&self.as_slice()).finish()
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> IntoIterator for GrowLock<T, A, L> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        let (ptr, len, cap, alloc) = self.into_parts_with_alloc();
+        // SAFETY: the buffer comes from the given `GrowLock`, with the
+        // same allocator and capacity.
+        let buf = unsafe {
+            RawGrowLock::from_nonnull_in(
+                ptr,
+                Cap::new_unchecked::<T>(cap),
+                alloc,
+            )
+        };
+        IntoIter {
+            buf,
+            start: 0,
+            end: len,
+        }
+    }
+}
+impl<'a, T, A: Allocator, L: WriteLock> IntoIterator
+    for &'a GrowLock<T, A, L>
+{
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -1,39 +1,79 @@
 #![doc = include_str!("../docs/lib-docs.md")]
+#![cfg_attr(feature = "std", doc = include_str!("../docs/lib-docs-std.md"))]
 //! # Examples
 //! ```
 #![doc = include_str!("../examples/basic_usage.rs")]
 //! ```
-#![feature(allocator_api)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
 extern crate core;
 
+pub mod allocator;
 mod cap;
+mod concurrent;
 pub mod error;
+pub mod ext;
+pub mod frozen;
+#[cfg(feature = "async")]
+pub mod future;
 pub mod guard;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+pub mod interner;
+pub mod iter;
+pub mod lock;
 mod macros;
+#[cfg(feature = "std")]
+pub mod map;
+#[cfg(all(feature = "mmap", unix, not(loom)))]
+pub mod mmap;
+#[cfg(feature = "async")]
+mod queue;
 mod raw;
-#[cfg(all(test, not(loom)))]
+#[cfg(feature = "std")]
+pub mod segmented;
+#[cfg(feature = "serde")]
+pub mod serde;
+#[cfg(all(
+    feature = "shm",
+    unix,
+    target_has_atomic = "64",
+    target_pointer_width = "64",
+    not(loom)
+))]
+pub mod shm;
+#[cfg(all(test, feature = "std", not(loom)))]
 mod tests;
 #[cfg(all(test, loom))]
 mod tests_loom;
+mod wait;
 
 #[cfg(not(loom))]
-use std::sync::{
-    LockResult, Mutex, TryLockResult,
-    atomic::{AtomicUsize, Ordering},
-};
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
 #[cfg(loom)]
-use loom::sync::{
-    LockResult, Mutex, TryLockResult,
-    atomic::{AtomicUsize, Ordering},
-};
+use loom::sync::atomic::{AtomicUsize, Ordering};
 use {
     crate::{
-        cap::Cap, error::TryReserveError, guard::GrowGuard,
+        allocator::{Allocator, Global},
+        cap::{Cap, is_zst},
+        concurrent::Producers,
+        error::{
+            FromVecError, LockResult, PoisonError, TryLockError,
+            TryLockResult, TryReserveError,
+        },
+        frozen::FrozenGrowLock,
+        guard::{GrowGuard, OwnedGrowGuard},
+        lock::{DefaultLock, LockState, WriteLock},
         raw::RawGrowLock,
+        wait::Waiters,
     },
-    std::{
-        alloc::{Allocator, Global},
+    alloc::{alloc::handle_alloc_error, boxed::Box, sync::Arc, vec::Vec},
+    core::{
         borrow::Borrow,
         fmt,
         hash::{Hash, Hasher},
@@ -41,19 +81,50 @@ use {
         ops,
         ptr::{self, NonNull},
         slice::{self, SliceIndex},
-        sync::{PoisonError, TryLockError},
     },
 };
 
+/// Items used by the macros of this crate, which must work without `std`.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec;
+}
+
+#[cfg(feature = "async")]
+use crate::{
+    future::{Subscriber, WaitLen, WriteAsync},
+    queue::WriteQueue,
+};
+
 #[doc = include_str!("../docs/growlock.md")]
 /// # Examples
 /// ```
 #[doc = include_str!("../examples/basic_usage.rs")]
 /// ```
-pub struct GrowLock<T, A: Allocator = Global> {
+pub struct GrowLock<T, A: Allocator = Global, L: WriteLock = DefaultLock> {
     buf: RawGrowLock<T, A>,
+    /// Number of published elements.
+    ///
+    /// # Publication protocol
+    /// `len` is what makes an element visible to readers, so it must
+    /// never be observed before the element it publishes:
+    /// * the writer initializes the element(s) first, then stores the new
+    ///   length with [`Ordering::Release`].
+    /// * readers load it with [`Ordering::Acquire`], so every element
+    ///   below the loaded length is guaranteed to be initialized and
+    ///   visible to them.
+    ///
+    /// The writer itself may load it with [`Ordering::Relaxed`], as the
+    /// write lock already orders it after the previous writer.
+    ///
+    /// Lock-free producers advance it with a CAS instead, see
+    /// [`Producers`].
     len: AtomicUsize,
-    mutex: Mutex<()>,
+    write_lock: LockState<L>,
+    producers: Producers,
+    waiters: Waiters,
+    #[cfg(feature = "async")]
+    writers: WriteQueue,
 }
 
 /// # Safety:
@@ -62,23 +133,26 @@ pub struct GrowLock<T, A: Allocator = Global> {
 /// the buffer.
 ///
 /// No thread can access the data while it's being moved.
-unsafe impl<T, A> Send for GrowLock<T, A>
+unsafe impl<T, A, L> Send for GrowLock<T, A, L>
 where
     T: Send,
     A: Send + Allocator,
+    L: WriteLock,
 {
 }
 /// # Safety:
 /// If both `T` and `A` are [`Sync`], there's no interior mutability
-/// outside the [`mutex`](Mutex) and the [`len`](AtomicUsize) (which are
-/// thread-safe).
+/// outside the write lock, the [`len`](AtomicUsize) and the lock-free
+/// producers (which are thread-safe).
 ///
-/// All writes to the buffer are handled along the [`mutex`](Mutex), and so
-/// this collection is [`Sync`]
-unsafe impl<T, A> Sync for GrowLock<T, A>
+/// All writes to the buffer are handled along the write lock, or
+/// happen in slots reserved by a single producer, and so this collection
+/// is [`Sync`]
+unsafe impl<T, A, L> Sync for GrowLock<T, A, L>
 where
     T: Sync + Send,
     A: Sync + Send + Allocator,
+    L: WriteLock,
 {
 }
 
@@ -135,23 +209,21 @@ impl<T> GrowLock<T> {
     /// * at least `len` elements starting from `ptr` need to be properly
     ///   initialized values of type `T`.
     #[inline]
+    #[must_use]
     pub unsafe fn from_parts(
         ptr: NonNull<T>,
-        len: AtomicUsize,
+        len: usize,
         capacity: usize,
     ) -> Self {
-        Self {
-            // SAFETY: the  safety contract must be upheld by the caller
-            buf: unsafe {
-                RawGrowLock::from_nonnull_in(
-                    ptr,
-                    Cap::new_unchecked::<T>(capacity),
-                    Global,
-                )
-            },
-            len,
-            mutex: Mutex::new(()),
-        }
+        // SAFETY: the  safety contract must be upheld by the caller
+        let buf = unsafe {
+            RawGrowLock::from_nonnull_in(
+                ptr,
+                Cap::new_unchecked::<T>(capacity),
+                Global,
+            )
+        };
+        Self::from_buf(buf, len)
     }
     /// Creates a new [`GrowLock<T>`] directly from a pointer, and
     /// a capacity.
@@ -169,24 +241,85 @@ impl<T> GrowLock<T> {
     /// * at least `len` elements starting from `ptr` need to be properly
     ///   initialized values of type `T`.
     #[inline]
+    #[must_use]
     pub unsafe fn from_raw_parts(
         ptr: *mut T,
-        len: AtomicUsize,
+        len: usize,
         capacity: usize,
     ) -> Self {
-        Self {
-            // SAFETY: the  safety contract must be upheld by the caller
-            buf: unsafe {
-                RawGrowLock::from_raw_in(
-                    ptr,
-                    Cap::new_unchecked::<T>(capacity),
-                    Global,
-                )
-            },
-            len,
-            mutex: Mutex::new(()),
+        // SAFETY: the  safety contract must be upheld by the caller
+        let buf = unsafe {
+            RawGrowLock::from_raw_in(
+                ptr,
+                Cap::new_unchecked::<T>(capacity),
+                Global,
+            )
+        };
+        Self::from_buf(buf, len)
+    }
+    /// Creates a new [`GrowLock<T>`] from the elements of a [`Vec`],
+    /// reallocating its buffer (if needed) to a capacity of exactly
+    /// `capacity`.
+    ///
+    /// Unlike `GrowLock::from(vec)`, which keeps the capacity of the
+    /// [`Vec`], this leaves room for the elements to come.
+    ///
+    /// # Errors
+    /// If any of these conditions happen, an error is returned (and the
+    /// [`Vec`] is dropped):
+    /// * the [`Vec`] has more than `capacity` elements
+    /// * `capacity * size_of::<T>` overflows `isize::MAX`
+    /// * memory is exhausted
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::{GrowLock, error::FromVecError};
+    ///
+    /// let lock = GrowLock::try_from_vec_with_capacity(vec![1, 2], 5).unwrap();
+    /// assert_eq!((&lock[..], lock.capacity()), (&[1, 2][..], 5));
+    ///
+    /// assert_eq!(
+    ///     GrowLock::try_from_vec_with_capacity(vec![1, 2, 3], 2),
+    ///     Err(FromVecError::TooManyElements { len: 3, capacity: 2 })
+    /// );
+    /// ```
+    pub fn try_from_vec_with_capacity(
+        vec: Vec<T>,
+        capacity: usize,
+    ) -> Result<Self, FromVecError> {
+        let len = vec.len();
+        if capacity < len {
+            return Err(FromVecError::TooManyElements { len, capacity });
         }
+        let Some(cap) = Cap::new::<T>(capacity) else {
+            return Err(TryReserveError::CapacityOverflow.into());
+        };
+        let mut lock = Self::from(vec);
+        // SAFETY: the `len` elements fit in `cap`.
+        unsafe { lock.buf.try_realloc(cap) }?;
+        lock.producers = Producers::new(len, lock.capacity());
+        Ok(lock)
+    }
+    /// Creates a full [`GrowLock<T>`] from a boxed slice, reusing its
+    /// allocation (no element is moved).
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    ///
+    /// let lock = GrowLock::from_boxed_slice(Box::new([1, 2, 3]));
+    /// assert_eq!(&lock[..], &[1, 2, 3]);
+    /// assert!(lock.is_full());
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn from_boxed_slice(boxed: Box<[T]>) -> Self {
+        Self::from(Vec::from(boxed))
     }
+}
+
+/// [`Global`] only methods
+impl<T, L: WriteLock> GrowLock<T, Global, L> {
     /// Decomposes a [`GrowLock<T>`] into its raw components:
     /// ([`NonNull`] pointer, length, capacity).
     ///
@@ -195,8 +328,8 @@ impl<T> GrowLock<T> {
     /// [`from_parts`](GrowLock::from_parts).
     #[inline]
     pub fn into_parts(self) -> (NonNull<T>, usize, usize) {
-        let mut this = ManuallyDrop::new(self);
-        (this.as_non_null(), this.len(), this.capacity())
+        let (ptr, len, cap, Global) = self.into_parts_with_alloc();
+        (ptr, len, cap)
     }
     /// Decomposes a [`GrowLock<T>`] into its raw components:
     /// (pointer, length, capacity).
@@ -206,12 +339,151 @@ impl<T> GrowLock<T> {
     /// [`from_raw_parts`](GrowLock::from_raw_parts).
     #[inline]
     pub fn into_raw_parts(self) -> (*mut T, usize, usize) {
-        let mut this = ManuallyDrop::new(self);
-        (this.as_mut_ptr(), this.len(), this.capacity())
+        let (ptr, len, cap, Global) = self.into_raw_parts_with_alloc();
+        (ptr, len, cap)
+    }
+    /// Converts the [`GrowLock`] into a boxed slice, shrinking its buffer
+    /// to fit.
+    ///
+    /// With the `allocator_api` feature, this is available with any
+    /// allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let boxed = grow_lock!(10, [1, 2, 3]).into_boxed_slice();
+    /// assert_eq!(&*boxed, &[1, 2, 3]);
+    /// ```
+    #[cfg(not(feature = "allocator_api"))]
+    #[inline]
+    #[must_use]
+    pub fn into_boxed_slice(self) -> Box<[T]> {
+        Vec::from(self).into_boxed_slice()
     }
 }
 
+/// Constructors, with the default write lock
 impl<T, A: Allocator> GrowLock<T, A> {
+    /// Creates a new [`GrowLock<T>`] in the provided allocator,
+    /// returning an error if the allocation fails
+    ///
+    /// # Errors
+    /// If any of these conditions happen, an error is returned:
+    /// * `cap * size_of::<T>` overflows [`isize::MAX`]
+    /// * memory is exhausted
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use growlock::{GrowLock, allocator::System};
+    ///
+    /// let lock: GrowLock<u32, _> = GrowLock::try_with_capacity_in(10, System).unwrap();
+    /// # }
+    /// ```
+    pub fn try_with_capacity_in(
+        capacity: usize,
+        alloc: A,
+    ) -> Result<Self, TryReserveError> {
+        let Some(cap) = Cap::new::<T>(capacity) else {
+            return Err(TryReserveError::CapacityOverflow);
+        };
+        let buf = RawGrowLock::try_with_capacity_in(cap, alloc)?;
+
+        Ok(Self::from_buf(buf, 0))
+    }
+
+    /// Creates a new [`GrowLock<T>`] in the provided allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use growlock::{GrowLock, allocator::System};
+    ///
+    /// let lock: GrowLock<u32, _> = GrowLock::with_capacity_in(10, System);
+    /// # }
+    /// ```
+    #[inline]
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        let Some(cap) = Cap::new::<T>(capacity) else {
+            panic!("{}", TryReserveError::CapacityOverflow);
+        };
+        let buf = RawGrowLock::with_capacity_in(cap, alloc);
+
+        Self::from_buf(buf, 0)
+    }
+    /// Creates a new [`GrowLock<T>`] directly from a [`NonNull`]
+    /// pointer, a capacity, and an allocator.
+    ///
+    /// # Safety
+    /// * `ptr` must be currently allocated with the given allocator
+    ///   `alloc`.
+    /// * `T` needs to have the same alignment as what `ptr` was allocated
+    ///   with.
+    /// * `size_of::<T>() * cap` must be the same as the size the pointer
+    ///   was allocated with.
+    /// * `capacity` needs to fit the layout size that the pointer was
+    ///   allocated with.
+    /// * the allocated size in bytes cannot exceed [`isize::MAX`] (the
+    ///   size is `self.capacity() * size_of::<T>`)
+    /// * `len` must be <= `capacity`
+    /// * at least `len` elements starting from `ptr` need to be properly
+    ///   initialized values of type `T`.
+    #[inline]
+    pub unsafe fn from_parts_in(
+        ptr: NonNull<T>,
+        len: usize,
+        capacity: usize,
+        alloc: A,
+    ) -> Self {
+        // SAFETY: the safety contract must be upheld by the caller
+        let buf = unsafe {
+            RawGrowLock::from_nonnull_in(
+                ptr,
+                Cap::new_unchecked::<T>(capacity),
+                alloc,
+            )
+        };
+        Self::from_buf(buf, len)
+    }
+    /// Creates a new [`GrowLock<T>`] directly from a pointer,
+    /// a capacity, and an allocator.
+    ///
+    /// # Safety
+    /// * `ptr` must be currently allocated with the given allocator
+    ///   `alloc`.
+    /// * `T` needs to have the same alignment as what `ptr` was allocated
+    ///   with.
+    /// * `size_of::<T>() * cap` must be the same as the size the pointer
+    ///   was allocated with.
+    /// * `capacity` needs to fit the layout size that the pointer was
+    ///   allocated with.
+    /// * the allocated size in bytes cannot exceed [`isize::MAX`]
+    /// * `len` must be <= `capacity`
+    /// * at least `len` elements starting from `ptr` need to be properly
+    ///   initialized values of type `T`.
+    #[inline]
+    pub unsafe fn from_raw_parts_in(
+        ptr: *mut T,
+        len: usize,
+        capacity: usize,
+        alloc: A,
+    ) -> Self {
+        // SAFETY: the  safety contract must be upheld by the caller
+        let buf = unsafe {
+            RawGrowLock::from_raw_in(
+                ptr,
+                Cap::new_unchecked::<T>(capacity),
+                alloc,
+            )
+        };
+        Self::from_buf(buf, len)
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> GrowLock<T, A, L> {
     /// Returns `true` if the [`GrowLock`] contains no elements, i.e. the
     /// `len` is zero.
     ///
@@ -300,7 +572,9 @@ impl<T, A: Allocator> GrowLock<T, A> {
     #[inline]
     #[must_use]
     pub fn len(&self) -> usize {
-        self.len.load(Ordering::Relaxed)
+        // Acquire: pairs with the Release store of the writer, so all the
+        // elements below `len` are visible to this thread.
+        self.len.load(Ordering::Acquire)
     }
 
     /// Returns a reference to the underlying allocator.
@@ -351,140 +625,292 @@ impl<T, A: Allocator> GrowLock<T, A> {
         //   do)
         // * the entire block of memory is within a single allocation
         // * at least `self.len()` number of elements are correctly
-        //   initialized.
+        //   initialized, and visible to this thread as `len()` is loaded
+        //   with `Acquire`.
         // * `capacity * size_of::<T>()` doesn't overflow `isize::MAX`, so
         //   neither does `self.len() * size_of::<T>()`
         unsafe { slice::from_raw_parts(self.as_ptr(), self.len()) }
     }
-
-    /// Creates a new [`GrowLock<T>`] in the provided allocator,
-    /// returning an error if the allocation fails
+    /// Extracts a mutable slice containing the entire vector up to
+    /// `self.len()`.
     ///
-    /// # Errors
-    /// If any of these conditions happen, an error is returned:
-    /// * `cap * size_of::<T>` overflows [`isize::MAX`]
-    /// * memory is exhausted
+    /// Holding `&mut self` proves that there are no readers nor writers,
+    /// so the elements can be modified.
     ///
     /// # Examples
     /// ```
-    /// #![feature(allocator_api)]
-    /// use growlock::GrowLock;
-    /// use std::alloc::System;
+    /// use growlock::grow_lock;
     ///
-    /// let lock: GrowLock<u32, _> = GrowLock::try_with_capacity_in(10, System).unwrap();
+    /// let mut lock = grow_lock!(5, [1, 2, 3]);
+    /// lock.as_mut_slice()[0] = 42;
+    /// assert_eq!(lock, [42, 2, 3]);
     /// ```
-    pub fn try_with_capacity_in(
-        capacity: usize,
-        alloc: A,
-    ) -> Result<Self, TryReserveError> {
-        let Some(cap) = Cap::new::<T>(capacity) else {
-            return Err(TryReserveError::CapacityOverflow);
-        };
-        let buf = RawGrowLock::try_with_capacity_in(cap, alloc)?;
-
-        Ok(Self {
-            buf,
-            len: AtomicUsize::new(0),
-            mutex: Mutex::new(()),
-        })
+    #[inline]
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: same as `as_slice`, and `&mut self` guarantees that no
+        // one else accesses the elements.
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), self.len()) }
     }
-
-    /// Creates a new [`GrowLock<T>`] in the provided allocator.
+    /// Returns a mutable reference to an element or subslice, or `None`
+    /// if the index is out of bounds.
     ///
     /// # Examples
     /// ```
-    /// #![feature(allocator_api)]
-    /// use growlock::GrowLock;
-    /// use std::alloc::System;
+    /// use growlock::grow_lock;
     ///
-    /// let lock: GrowLock<u32, _> = GrowLock::with_capacity_in(10, System);
+    /// let mut lock = grow_lock!(5, [1, 2, 3]);
+    /// if let Some(elem) = lock.get_mut(1) {
+    ///     *elem = 42;
+    /// }
+    /// assert_eq!(lock, [1, 42, 3]);
+    /// assert_eq!(lock.get_mut(3), None);
     /// ```
     #[inline]
     #[must_use]
-    #[allow(clippy::missing_panics_doc)]
-    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
-        let Some(cap) = Cap::new::<T>(capacity) else {
-            panic!("{}", TryReserveError::CapacityOverflow);
-        };
-        let buf = RawGrowLock::with_capacity_in(cap, alloc);
+    pub fn get_mut<I: SliceIndex<[T]>>(
+        &mut self,
+        index: I,
+    ) -> Option<&mut I::Output> {
+        self.as_mut_slice().get_mut(index)
+    }
 
-        Self {
-            buf,
-            len: AtomicUsize::new(0),
-            mutex: Mutex::new(()),
+    /// Shortens the [`GrowLock`], keeping the first `len` elements and
+    /// dropping the rest. This has no effect if `len` is greater than the
+    /// current length.
+    ///
+    /// The capacity is left untouched, so the buffer can be reused.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let mut lock = grow_lock!(5, [1, 2, 3]);
+    /// lock.truncate(1);
+    /// assert_eq!(lock, [1]);
+    /// lock.write().unwrap().push(4);
+    /// assert_eq!(lock, [1, 4]);
+    /// ```
+    pub fn truncate(&mut self, len: usize) {
+        let old_len = self.len();
+        if len >= old_len {
+            return;
+        }
+        // the length is updated first, so a panic while dropping the
+        // elements leaks the remaining ones instead of dropping them
+        // twice.
+        self.set_len_mut(len);
+        // SAFETY: the elements in `len..old_len` are initialized, and they
+        // are not reachable anymore.
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                self.as_mut_ptr().add(len),
+                old_len - len,
+            ));
         }
     }
-    /// Creates a new [`GrowLock<T>`] directly from a [`NonNull`]
-    /// pointer, a capacity, and an allocator.
+    /// Drops all the elements, keeping the capacity.
     ///
-    /// # Safety
-    /// * `ptr` must be currently allocated with the given allocator
-    ///   `alloc`.
-    /// * `T` needs to have the same alignment as what `ptr` was allocated
-    ///   with.
-    /// * `size_of::<T>() * cap` must be the same as the size the pointer
-    ///   was allocated with.
-    /// * `capacity` needs to fit the layout size that the pointer was
-    ///   allocated with.
-    /// * the allocated size in bytes cannot exceed [`isize::MAX`] (the
-    ///   size is `self.capacity() * size_of::<T>`)
-    /// * `len` must be <= `capacity`
-    /// * at least `len` elements starting from `ptr` need to be properly
-    ///   initialized values of type `T`.
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let mut lock = grow_lock!(5, [1, 2, 3]);
+    /// lock.clear();
+    /// assert!(lock.is_empty());
+    /// assert_eq!(lock.capacity(), 5);
+    /// ```
     #[inline]
-    pub unsafe fn from_parts_in(
-        ptr: NonNull<T>,
-        len: usize,
-        capacity: usize,
-        alloc: A,
-    ) -> Self {
-        Self {
-            // SAFETY: the safety contract must be upheld by the caller
-            buf: unsafe {
-                RawGrowLock::from_nonnull_in(
-                    ptr,
-                    Cap::new_unchecked::<T>(capacity),
-                    alloc,
-                )
-            },
-            len: AtomicUsize::new(len),
-            mutex: Mutex::new(()),
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+    /// Retains only the elements for which `f` returns `true`, keeping
+    /// their order.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let mut lock = grow_lock!(5, [1, 2, 3, 4]);
+    /// lock.retain(|n| n % 2 == 0);
+    /// assert_eq!(lock, [2, 4]);
+    /// ```
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        /// Restores the length on drop, after shifting the unprocessed
+        /// elements after the retained ones, so a panic in `f` or in a
+        /// `Drop` keeps them instead of leaking them.
+        struct Guard<'a, T, A: Allocator, L: WriteLock> {
+            lock: &'a mut GrowLock<T, A, L>,
+            len: usize,
+            processed: usize,
+            kept: usize,
+        }
+        impl<T, A: Allocator, L: WriteLock> Drop for Guard<'_, T, A, L> {
+            fn drop(&mut self) {
+                let ptr = self.lock.as_mut_ptr();
+                // SAFETY: the elements in `processed..len` are initialized,
+                // and `kept <= processed`.
+                unsafe {
+                    ptr::copy(
+                        ptr.add(self.processed),
+                        ptr.add(self.kept),
+                        self.len - self.processed,
+                    );
+                }
+                self.lock
+                    .set_len_mut(self.kept + self.len - self.processed);
+            }
+        }
+
+        let len = self.len();
+        // the elements are not reachable while they are processed, so
+        // they are not dropped twice.
+        self.set_len_mut(0);
+        let mut guard = Guard {
+            lock: self,
+            len,
+            processed: 0,
+            kept: 0,
+        };
+        let ptr = guard.lock.as_mut_ptr();
+        while guard.processed < len {
+            // SAFETY: the elements in `processed..len` are initialized, and
+            // the ones in `..kept` are the retained ones, so
+            // `kept <= processed`.
+            unsafe {
+                let elem = ptr.add(guard.processed);
+                if f(&*elem) {
+                    ptr::copy(elem, ptr.add(guard.kept), 1);
+                    guard.processed += 1;
+                    guard.kept += 1;
+                } else {
+                    // counted first, so a panicking `Drop` doesn't drop it
+                    // again.
+                    guard.processed += 1;
+                    ptr::drop_in_place(elem);
+                }
+            }
         }
     }
-    /// Creates a new [`GrowLock<T>`] directly from a pointer,
-    /// a capacity, and an allocator.
+
+    /// Reserves capacity for at least `additional` more elements,
+    /// reallocating the buffer in its allocator if needed.
     ///
-    /// # Safety
-    /// * `ptr` must be currently allocated with the given allocator
-    ///   `alloc`.
-    /// * `T` needs to have the same alignment as what `ptr` was allocated
-    ///   with.
-    /// * `size_of::<T>() * cap` must be the same as the size the pointer
-    ///   was allocated with.
-    /// * `capacity` needs to fit the layout size that the pointer was
-    ///   allocated with.
-    /// * the allocated size in bytes cannot exceed [`isize::MAX`]
-    /// * `len` must be <= `capacity`
-    /// * at least `len` elements starting from `ptr` need to be properly
-    ///   initialized values of type `T`.
+    /// Unlike [`Vec::reserve`], this doesn't reserve more than requested:
+    /// the capacity is meant to be chosen by the caller.
+    ///
+    /// # Panics
+    /// Panics if the new capacity exceeds `isize::MAX` _bytes_.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let mut lock = grow_lock!(3, [1, 2, 3]);
+    /// lock.reserve(2);
+    /// assert_eq!(lock.capacity(), 5);
+    /// lock.write().unwrap().extend([4, 5]);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        match self.try_reserve(additional) {
+            Ok(()) => {}
+            Err(e @ TryReserveError::CapacityOverflow) => panic!("{e}"),
+            Err(TryReserveError::AllocError(layout)) => {
+                handle_alloc_error(layout)
+            }
+        }
+    }
+    /// Tries to reserve capacity for at least `additional` more elements,
+    /// reallocating the buffer in its allocator if needed.
+    ///
+    /// Unlike [`Vec::try_reserve`], this doesn't reserve more than
+    /// requested: the capacity is meant to be chosen by the caller.
+    ///
+    /// # Errors
+    /// If any of these conditions happen, an error is returned and the
+    /// [`GrowLock`] is left untouched:
+    /// * the new capacity exceeds `isize::MAX` _bytes_
+    /// * memory is exhausted
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::{GrowLock, error::TryReserveError};
+    ///
+    /// let mut lock = GrowLock::<u64>::with_capacity(3);
+    /// assert_eq!(lock.try_reserve(5), Ok(()));
+    /// assert_eq!(lock.capacity(), 5);
+    /// assert_eq!(
+    ///     lock.try_reserve(usize::MAX),
+    ///     Err(TryReserveError::CapacityOverflow)
+    /// );
+    /// ```
+    pub fn try_reserve(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), TryReserveError> {
+        let len = self.len();
+        let Some(capacity) = len.checked_add(additional) else {
+            return Err(TryReserveError::CapacityOverflow);
+        };
+        if capacity <= self.capacity() {
+            return Ok(());
+        }
+        let Some(cap) = Cap::new::<T>(capacity) else {
+            return Err(TryReserveError::CapacityOverflow);
+        };
+        // SAFETY: the buffer grows.
+        unsafe { self.buf.try_realloc(cap) }?;
+        self.producers = Producers::new(len, self.capacity());
+        Ok(())
+    }
+    /// Shrinks the capacity to the length, reallocating the buffer in its
+    /// allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let mut lock = grow_lock!(10, [1, 2, 3]);
+    /// lock.shrink_to_fit();
+    /// assert_eq!(lock.capacity(), 3);
+    /// assert!(lock.is_full());
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        let len = self.len();
+        // SAFETY: `len` is not greater than the capacity.
+        let cap = unsafe { Cap::new_unchecked::<T>(len) };
+        // SAFETY: the elements past `len` are not initialized.
+        if let Err(e) = unsafe { self.buf.try_realloc(cap) } {
+            match e {
+                TryReserveError::CapacityOverflow => unreachable!(),
+                TryReserveError::AllocError(layout) => {
+                    handle_alloc_error(layout)
+                }
+            }
+        }
+        self.producers = Producers::new(len, self.capacity());
+    }
+    /// Sets the length through `&mut self`, resetting the lock-free
+    /// producers to the new length.
+    fn set_len_mut(&mut self, len: usize) {
+        self.len.store(len, Ordering::Relaxed);
+        // the `published` bitmap may hold the slots past `len`, which
+        // would be published again by the next producers.
+        self.producers = Producers::new(len, self.capacity());
+    }
+
+    /// Creates a new [`GrowLock<T>`] from its buffer, with the first `len`
+    /// elements already initialized.
     #[inline]
-    pub unsafe fn from_raw_parts_in(
-        ptr: *mut T,
-        len: AtomicUsize,
-        capacity: usize,
-        alloc: A,
-    ) -> Self {
+    fn from_buf(buf: RawGrowLock<T, A>, len: usize) -> Self {
         Self {
-            // SAFETY: the  safety contract must be upheld by the caller
-            buf: unsafe {
-                RawGrowLock::from_raw_in(
-                    ptr,
-                    Cap::new_unchecked::<T>(capacity),
-                    alloc,
-                )
-            },
-            len,
-            mutex: Mutex::new(()),
+            producers: Producers::new(len, buf.capacity()),
+            buf,
+            len: AtomicUsize::new(len),
+            write_lock: LockState::new(),
+            waiters: Waiters::new(),
+            #[cfg(feature = "async")]
+            writers: WriteQueue::new(),
         }
     }
 
@@ -520,14 +946,9 @@ impl<T, A: Allocator> GrowLock<T, A> {
     /// ```
     #[inline]
     #[doc(alias = "lock")]
-    pub fn write(&self) -> LockResult<GrowGuard<'_, T, A>> {
-        match self.mutex.lock() {
-            Ok(guard) => Ok(GrowGuard::new(self, guard)),
-            Err(e) => {
-                let guard = e.into_inner();
-                Err(PoisonError::new(GrowGuard::new(self, guard)))
-            }
-        }
+    pub fn write(&self) -> LockResult<GrowGuard<'_, T, A, L>> {
+        let permit = self.write_lock.lock();
+        self.check_poison(GrowGuard::new(self, permit))
     }
 
     /// Attempts to lock this [`GrowLock`] with exclusive write access.
@@ -573,18 +994,522 @@ impl<T, A: Allocator> GrowLock<T, A> {
     /// [`WouldBlock`]: TryLockError::WouldBlock
     #[inline]
     #[doc(alias = "try_lock")]
-    pub fn try_write(&self) -> TryLockResult<GrowGuard<'_, T, A>> {
-        match self.mutex.try_lock() {
-            Ok(guard) => Ok(GrowGuard::new(self, guard)),
-            Err(TryLockError::Poisoned(e)) => {
-                let guard = e.into_inner();
-                Err(TryLockError::Poisoned(PoisonError::new(
-                    GrowGuard::new(self, guard),
-                )))
+    pub fn try_write(&self) -> TryLockResult<GrowGuard<'_, T, A, L>> {
+        let permit =
+            self.write_lock.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self.check_poison(GrowGuard::new(self, permit))?)
+    }
+    /// Attempts to lock this [`GrowLock`] with exclusive write access,
+    /// blocking the current thread for at most `timeout`.
+    ///
+    /// This is the same as [`GrowLock::try_write_until`], with a deadline
+    /// `timeout` from now.
+    ///
+    /// # Errors
+    /// This function will return the [`Poisoned`] error if the
+    /// [`GrowLock`] is poisoned, as [`GrowLock::try_write`].
+    ///
+    /// This function will return the [`WouldBlock`] error if the
+    /// [`GrowLock`] could not be acquired before the timeout expired.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    /// use std::time::Duration;
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// let guard = lock.write().unwrap();
+    /// let timeout = Duration::from_millis(10);
+    /// assert!(lock.try_write_for(timeout).is_err());
+    /// drop(guard);
+    /// lock.try_write_for(timeout).unwrap().push(1);
+    /// ```
+    ///
+    /// [`Poisoned`]: TryLockError::Poisoned
+    /// [`WouldBlock`]: TryLockError::WouldBlock
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn try_write_for(
+        &self,
+        timeout: Duration,
+    ) -> TryLockResult<GrowGuard<'_, T, A, L>> {
+        // if the deadline overflows, we wait forever.
+        self.try_write_deadline(Instant::now().checked_add(timeout))
+    }
+    /// Attempts to lock this [`GrowLock`] with exclusive write access,
+    /// blocking the current thread until `deadline` at most.
+    ///
+    /// If the lock is free, it is acquired even if `deadline` has already
+    /// passed.
+    ///
+    /// # Errors
+    /// This function will return the [`Poisoned`] error if the
+    /// [`GrowLock`] is poisoned, as [`GrowLock::try_write`].
+    ///
+    /// This function will return the [`WouldBlock`] error if the
+    /// [`GrowLock`] could not be acquired before `deadline`.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    /// use std::time::{Duration, Instant};
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// let deadline = Instant::now() + Duration::from_millis(10);
+    /// let mut guard = lock.try_write_until(deadline).unwrap();
+    /// guard.push(1);
+    /// assert!(lock.try_write_until(deadline).is_err());
+    /// ```
+    ///
+    /// [`Poisoned`]: TryLockError::Poisoned
+    /// [`WouldBlock`]: TryLockError::WouldBlock
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn try_write_until(
+        &self,
+        deadline: Instant,
+    ) -> TryLockResult<GrowGuard<'_, T, A, L>> {
+        self.try_write_deadline(Some(deadline))
+    }
+    #[cfg(feature = "std")]
+    fn try_write_deadline(
+        &self,
+        deadline: Option<Instant>,
+    ) -> TryLockResult<GrowGuard<'_, T, A, L>> {
+        let permit = match deadline {
+            None => self.write_lock.lock(),
+            Some(deadline) => self
+                .write_lock
+                .try_lock_until(deadline)
+                .ok_or(TryLockError::WouldBlock)?,
+        };
+        Ok(self.check_poison(GrowGuard::new(self, permit))?)
+    }
+    /// Returns `true` if the [`GrowLock`] is poisoned, i.e. a writer
+    /// panicked while holding the write lock.
+    ///
+    /// Poisoning only affects the writers: the published elements are
+    /// never torn, so the readers are not affected.
+    ///
+    /// If another thread is active, the lock can still become poisoned at
+    /// any time. You should not trust a `false` value for program
+    /// correctness without additional synchronization.
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use growlock::GrowLock;
+    /// use std::thread;
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// let _ = thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let mut guard = lock.write().unwrap();
+    ///         guard.push(1);
+    ///         panic!();
+    ///     })
+    ///     .join()
+    /// });
+    /// assert!(lock.is_poisoned());
+    /// assert_eq!(&lock[..], &[1]);
+    /// # }
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn is_poisoned(&self) -> bool {
+        self.write_lock.is_poisoned()
+    }
+    /// Clears the poisoned state of the [`GrowLock`].
+    ///
+    /// If the lock is poisoned, it will remain poisoned until this
+    /// function is called. This allows recovering from a poisoned state
+    /// and marking that the writers have recovered (e.g. by checking the
+    /// published elements).
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use growlock::GrowLock;
+    /// use std::thread;
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// let _ = thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let _guard = lock.write().unwrap();
+    ///         panic!();
+    ///     })
+    ///     .join()
+    /// });
+    /// assert!(lock.write().is_err());
+    /// lock.clear_poison();
+    /// lock.write().unwrap().push(1);
+    /// # }
+    /// ```
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.write_lock.clear_poison();
+    }
+    /// Disables the poisoning of the [`GrowLock`], clearing it: the
+    /// writers which panic don't poison it anymore, so
+    /// [`GrowLock::write`] and the other locking methods never return
+    /// a [`PoisonError`].
+    ///
+    /// As the published elements are never torn, the writers can
+    /// always resume after a panic.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    /// use std::thread;
+    ///
+    /// let lock = GrowLock::with_capacity(5).without_poisoning();
+    /// let _ = thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let _guard = lock.write().unwrap();
+    ///         panic!();
+    ///     })
+    ///     .join()
+    /// });
+    /// assert!(!lock.is_poisoned());
+    /// lock.write().unwrap().push(1);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn without_poisoning(mut self) -> Self {
+        self.write_lock.disable_poisoning();
+        self
+    }
+    /// Wraps the guard of a writer in a [`PoisonError`] if the
+    /// [`GrowLock`] is poisoned.
+    #[inline]
+    fn check_poison<G>(&self, guard: G) -> LockResult<G> {
+        if self.write_lock.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+    /// Locks this [`GrowLock`] for writes, blocking the current thread
+    /// until it can be acquired, as [`GrowLock::write`].
+    ///
+    /// The returned guard keeps a clone of the [`Arc`] instead of
+    /// borrowing it, so it can outlive `self` and be stored anywhere.
+    ///
+    /// # Errors
+    /// This function will return an error if the [`GrowLock`] is poisoned,
+    /// as [`GrowLock::write`].
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::{GrowLock, guard::OwnedGrowGuard};
+    /// use std::sync::Arc;
+    ///
+    /// struct Writer {
+    ///     guard: OwnedGrowGuard<i32>,
+    /// }
+    ///
+    /// let lock = Arc::new(GrowLock::with_capacity(5));
+    /// let mut writer = Writer { guard: lock.write_arc().unwrap() };
+    /// writer.guard.push(1);
+    /// assert!(lock.try_write().is_err());
+    /// drop(writer);
+    /// assert_eq!(&lock[..], &[1]);
+    /// ```
+    #[inline]
+    pub fn write_arc(
+        self: &Arc<Self>,
+    ) -> LockResult<OwnedGrowGuard<T, A, L>> {
+        let permit = self.write_lock.lock();
+        self.check_poison(OwnedGrowGuard::new(Arc::clone(self), permit))
+    }
+    /// Attempts to lock this [`GrowLock`] with exclusive write access, as
+    /// [`GrowLock::try_write`], returning an owned guard as
+    /// [`GrowLock::write_arc`].
+    ///
+    /// # Errors
+    /// This function will return the [`Poisoned`] error if the
+    /// [`GrowLock`] is poisoned, and the [`WouldBlock`] error if it was
+    /// already locked, as [`GrowLock::try_write`].
+    ///
+    /// [`Poisoned`]: TryLockError::Poisoned
+    /// [`WouldBlock`]: TryLockError::WouldBlock
+    #[inline]
+    pub fn try_write_arc(
+        self: &Arc<Self>,
+    ) -> TryLockResult<OwnedGrowGuard<T, A, L>> {
+        let permit =
+            self.write_lock.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self
+            .check_poison(OwnedGrowGuard::new(Arc::clone(self), permit))?)
+    }
+    /// Returns a future locking this [`GrowLock`] for writes, resolving
+    /// once the lock is acquired. Note that this does not lock the
+    /// [`GrowLock`] for reads.
+    ///
+    /// This is the asynchronous version of [`GrowLock::write`]: it doesn't
+    /// block the thread, so it can be awaited on any executor. The lock is
+    /// handed over to the asynchronous writers in the order they started
+    /// waiting, while [`GrowLock::write`] and [`GrowLock::try_write`] may
+    /// take it at any time.
+    ///
+    /// # Errors
+    /// The future resolves to an error if the [`GrowLock`] is poisoned, as
+    /// [`GrowLock::write`]. The acquired lock guard will be contained in
+    /// the returned error.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    ///
+    /// async fn append(lock: &GrowLock<i32>, values: &[i32]) {
+    ///     let mut guard = lock.write_async().await.unwrap();
+    ///     guard.extend(values.iter().copied());
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[inline]
+    pub const fn write_async(&self) -> WriteAsync<'_, T, A, L> {
+        WriteAsync::new(self)
+    }
+    /// Appends an element to the back of the [`GrowLock`] without locking
+    /// it for writes, returning the index of the element.
+    ///
+    /// Any number of threads can call this at the same time: each of them
+    /// reserves a slot with an atomic fetch-add, writes its value there,
+    /// and then publishes it. As the readers can only see a contiguous
+    /// prefix of the elements, an element becomes visible once all the
+    /// slots before it are published too.
+    ///
+    /// This can be mixed with the [`GrowGuard`] writes: while a guard is
+    /// alive, this waits for it to be dropped and then pushes through the
+    /// [write lock](GrowLock::write), so calling this while holding a
+    /// guard on the same thread deadlocks. As published elements are never
+    /// torn, a poisoned lock is ignored here.
+    ///
+    /// The first call allocates a bitmap with one bit for each slot of the
+    /// capacity, i.e. `capacity / 8` bytes (128 MiB for 2³³ slots), to
+    /// track the written slots. It is only freed when the [`GrowLock`] is
+    /// dropped or taken apart (e.g. by [`GrowLock::freeze`]). Zero-sized
+    /// types don't need it.
+    ///
+    /// # Errors
+    /// Returns the value back if the [`GrowLock`] is full.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let lock = Arc::new(GrowLock::with_capacity(8));
+    /// let handles: Vec<_> = (0..4)
+    ///     .map(|i| {
+    ///         let lock = Arc::clone(&lock);
+    ///         thread::spawn(move || lock.push_concurrent(i).unwrap())
+    ///     })
+    ///     .collect();
+    /// for handle in handles {
+    ///     let index = handle.join().unwrap();
+    ///     assert!(index < 4);
+    /// }
+    ///
+    /// let mut elems = lock.to_vec();
+    /// elems.sort_unstable();
+    /// assert_eq!(elems, [0, 1, 2, 3]);
+    /// ```
+    pub fn push_concurrent(&self, value: T) -> Result<usize, T> {
+        if !self.producers.enter() {
+            // a guard is alive: wait for it, and push through our own.
+            let mut guard =
+                self.write().unwrap_or_else(PoisonError::into_inner);
+            if guard.is_full() {
+                return Err(value);
+            }
+            let index = guard.len();
+            guard.push(value);
+            return Ok(index);
+        }
+
+        let cap = self.capacity();
+        let index = if is_zst::<T>() {
+            // there is nothing to write, so a ZST is published as soon as
+            // its slot is reserved.
+            let mut len = self.len.load(Ordering::Relaxed);
+            loop {
+                let Some(new_len) = len.checked_add(1) else {
+                    break None;
+                };
+                match self.len.compare_exchange_weak(
+                    len,
+                    new_len,
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(len) => break Some(len),
+                    Err(actual) => len = actual,
+                }
             }
+        } else {
+            Some(self.producers.reserve()).filter(|&index| index < cap)
+        };
+        let Some(index) = index else {
+            self.producers.leave();
+            return Err(value);
+        };
 
-            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        // SAFETY:
+        // * `index` is in the allocated block, and no one else reserved
+        //   this slot.
+        // * the slot is initialized before being published.
+        unsafe {
+            self.as_non_null_ref().add(index).write(value);
+            if !is_zst::<T>() {
+                self.producers.publish(index, cap, &self.len);
+            }
         }
+        self.producers.leave();
+        self.waiters.notify();
+        Ok(index)
+    }
+
+    /// Blocks the current thread until the [`GrowLock`] has at least `n`
+    /// elements, returning its length.
+    ///
+    /// Unlike polling [`GrowLock::len`], this parks the thread until a
+    /// writer publishes enough elements. The waiters are woken up once per
+    /// batch: when a write guard is dropped, and after each bulk write
+    /// (`extend`, `extend_from_slice`, a committed stage...) or
+    /// [`push_concurrent`](GrowLock::push_concurrent). Each wake-up costs
+    /// the writer a fence, and only takes a lock if someone is waiting;
+    /// [`push`](GrowGuard::push) itself pays nothing.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than the capacity, as the [`GrowLock`]
+    /// could never reach that length.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let lock = Arc::new(GrowLock::with_capacity(5));
+    /// let writer = thread::spawn({
+    ///     let lock = Arc::clone(&lock);
+    ///     move || lock.write().unwrap().extend([1, 2, 3])
+    /// });
+    ///
+    /// assert!(lock.wait_for_len(3) >= 3);
+    /// assert_eq!(&lock[..3], &[1, 2, 3]);
+    /// # writer.join().unwrap();
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn wait_for_len(&self, n: usize) -> usize {
+        self.assert_reachable(n);
+        // SAFETY: without a deadline, `wait` only returns once the length
+        // is reached.
+        unsafe { self.waiters.wait(&self.len, n, None).unwrap_unchecked() }
+    }
+
+    /// Blocks the current thread until the [`GrowLock`] has at least `n`
+    /// elements, or until `timeout` has elapsed.
+    ///
+    /// Returns the length of the [`GrowLock`], or `None` if the timeout
+    /// elapsed first. See [`GrowLock::wait_for_len`].
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than the capacity, as the [`GrowLock`]
+    /// could never reach that length.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    /// use std::time::Duration;
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// lock.write().unwrap().push(1);
+    ///
+    /// assert_eq!(lock.wait_for_len_timeout(1, Duration::ZERO), Some(1));
+    /// let timeout = Duration::from_millis(10);
+    /// assert_eq!(lock.wait_for_len_timeout(2, timeout), None);
+    /// ```
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn wait_for_len_timeout(
+        &self,
+        n: usize,
+        timeout: Duration,
+    ) -> Option<usize> {
+        self.assert_reachable(n);
+        // if the deadline overflows, we wait forever.
+        let deadline = Instant::now().checked_add(timeout);
+        self.waiters.wait(&self.len, n, deadline)
+    }
+    /// Returns a future resolving to the length of the [`GrowLock`] once
+    /// it has at least `n` elements.
+    ///
+    /// This is the asynchronous version of [`GrowLock::wait_for_len`]: it
+    /// doesn't block the thread, so it can be awaited on any executor.
+    ///
+    /// # Panics
+    /// Panics if `n` is greater than the capacity, as the [`GrowLock`]
+    /// could never reach that length.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    ///
+    /// async fn first_three(lock: &GrowLock<i32>) -> &[i32] {
+    ///     lock.wait_len(3).await;
+    ///     &lock[..3]
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[inline]
+    #[track_caller]
+    pub fn wait_len(&self, n: usize) -> WaitLen<'_, T, A, L> {
+        self.assert_reachable(n);
+        WaitLen::new(self, n)
+    }
+    /// Returns a [`Stream`](futures_core::Stream) of the elements
+    /// published after this call.
+    ///
+    /// See [`GrowLock::subscribe_from`].
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    ///
+    /// async fn log(lock: &GrowLock<String>) {
+    ///     let mut subscriber = lock.subscribe();
+    ///     while let Some(line) = subscriber.next().await {
+    ///         println!("{line}");
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn subscribe(&self) -> Subscriber<'_, T, A, L> {
+        Subscriber::new(self, self.len())
+    }
+    /// Returns a [`Stream`](futures_core::Stream) of the elements starting
+    /// at `index`, yielding each of them as soon as it is published.
+    ///
+    /// The stream ends once the [`GrowLock`] is full and all of its
+    /// elements have been yielded.
+    #[cfg(feature = "async")]
+    #[inline]
+    pub fn subscribe_from(&self, index: usize) -> Subscriber<'_, T, A, L> {
+        Subscriber::new(self, index)
+    }
+    #[cfg(feature = "std")]
+    #[inline]
+    #[track_caller]
+    fn assert_reachable(&self, n: usize) {
+        assert!(
+            n <= self.capacity(),
+            "cannot wait for a length of {n}, the capacity is {}",
+            self.capacity()
+        );
     }
     /// Decomposes a [`GrowLock<T>`] into its raw components:
     /// ([`NonNull`] pointer, length, capacity, allocator).
@@ -600,6 +1525,15 @@ impl<T, A: Allocator> GrowLock<T, A> {
         // SAFETY: `this.allocator()` is a reference
         // so all precondition are satisfied.
         let alloc = unsafe { ptr::read(this.allocator()) };
+        // SAFETY: `this` is never used again, and only the buffer and the
+        // allocator are moved out, so the other fields are dropped once.
+        unsafe {
+            ptr::drop_in_place(&raw mut this.write_lock);
+            ptr::drop_in_place(&raw mut this.producers);
+            ptr::drop_in_place(&raw mut this.waiters);
+            #[cfg(feature = "async")]
+            ptr::drop_in_place(&raw mut this.writers);
+        }
         (ptr, len, cap, alloc)
     }
     /// Decomposes a [`GrowLock<T>`] into its raw components:
@@ -614,6 +1548,90 @@ impl<T, A: Allocator> GrowLock<T, A> {
         let ptr = ptr.as_ptr();
         (ptr, len, cap, alloc)
     }
+    /// Moves the elements of the [`GrowLock`] under another write lock
+    /// backend, see the [`lock`] module.
+    ///
+    /// This keeps the same buffer, so no element is moved, and the
+    /// poisoning of the lock (see [`GrowLock::is_poisoned`]).
+    ///
+    /// # Examples
+    /// ```
+    /// # #[cfg(feature = "std")] {
+    /// use growlock::{GrowLock, lock::StdLock};
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// lock.write().unwrap().push(1);
+    /// let lock = lock.with_lock::<StdLock>();
+    /// assert_eq!(&lock[..], &[1]);
+    /// # }
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_lock<L2: WriteLock>(self) -> GrowLock<T, A, L2> {
+        let write_lock = self.write_lock.with_raw();
+        let (ptr, len, cap, alloc) = self.into_parts_with_alloc();
+        // SAFETY: the buffer comes from the given `GrowLock`, with the
+        // same allocator and capacity.
+        let buf = unsafe {
+            RawGrowLock::from_nonnull_in(
+                ptr,
+                Cap::new_unchecked::<T>(cap),
+                alloc,
+            )
+        };
+        let mut lock = GrowLock::from_buf(buf, len);
+        lock.write_lock = write_lock;
+        lock
+    }
+    /// Converts the [`GrowLock`] into a [`FrozenGrowLock`], which can't be
+    /// written anymore.
+    ///
+    /// This keeps the same buffer, so no element is moved, while the write
+    /// lock is dropped.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::GrowLock;
+    ///
+    /// let lock = GrowLock::with_capacity(5);
+    /// lock.write().unwrap().extend([1, 2, 3]);
+    /// let frozen = lock.freeze();
+    /// assert_eq!(&frozen[..], &[1, 2, 3]);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn freeze(self) -> FrozenGrowLock<T, A> {
+        let (ptr, len, cap, alloc) = self.into_parts_with_alloc();
+        // SAFETY: the buffer comes from the given `GrowLock`, with the
+        // same allocator and capacity.
+        let buf = unsafe {
+            RawGrowLock::from_nonnull_in(
+                ptr,
+                Cap::new_unchecked::<T>(cap),
+                alloc,
+            )
+        };
+        FrozenGrowLock::from_buf(buf, len)
+    }
+    /// Converts the [`GrowLock`] into a boxed slice, shrinking its buffer
+    /// to fit in the same allocator.
+    ///
+    /// Without the `allocator_api` feature, this is only available with
+    /// the [`Global`] allocator.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::grow_lock;
+    ///
+    /// let boxed = grow_lock!(10, [1, 2, 3]).into_boxed_slice();
+    /// assert_eq!(&*boxed, &[1, 2, 3]);
+    /// ```
+    #[cfg(feature = "allocator_api")]
+    #[inline]
+    #[must_use]
+    pub fn into_boxed_slice(self) -> Box<[T], A> {
+        Vec::from(self).into_boxed_slice()
+    }
     #[inline]
     pub fn ptr_eq(&self, rhs: &Self) -> bool {
         self.as_ptr() == rhs.as_ptr()
@@ -624,7 +1642,7 @@ impl<T, A: Allocator> GrowLock<T, A> {
     }
 }
 
-impl<T, A: Allocator> Drop for GrowLock<T, A> {
+impl<T, A: Allocator, L: WriteLock> Drop for GrowLock<T, A, L> {
     fn drop(&mut self) {
         // if `T::IS_ZST` then `capacity()` returns `usize::MAX`
         if self.capacity() == 0 {
@@ -641,30 +1659,31 @@ impl<T, A: Allocator> Drop for GrowLock<T, A> {
     }
 }
 
-impl<T, A: Allocator> ops::Deref for GrowLock<T, A> {
+impl<T, A: Allocator, L: WriteLock> ops::Deref for GrowLock<T, A, L> {
     type Target = [T];
     #[inline]
     fn deref(&self) -> &[T] {
         self.as_slice()
     }
 }
-impl<T, A: Allocator> Borrow<[T]> for GrowLock<T, A> {
+impl<T, A: Allocator, L: WriteLock> Borrow<[T]> for GrowLock<T, A, L> {
     #[inline]
     fn borrow(&self) -> &[T] {
         self.as_slice()
     }
 }
-impl<T, A: Allocator> AsRef<[T]> for GrowLock<T, A> {
+impl<T, A: Allocator, L: WriteLock> AsRef<[T]> for GrowLock<T, A, L> {
     #[inline]
     fn as_ref(&self) -> &[T] {
         self.as_slice()
     }
 }
 
-impl<T, I, A> ops::Index<I> for GrowLock<T, A>
+impl<T, I, A, L> ops::Index<I> for GrowLock<T, A, L>
 where
     I: SliceIndex<[T]>,
     A: Allocator,
+    L: WriteLock,
 {
     type Output = <I as SliceIndex<[T]>>::Output;
     #[inline]
@@ -672,16 +1691,95 @@ where
         ops::Index::index(&**self, index)
     }
 }
-impl<T, A: Allocator + Default> Default for GrowLock<T, A> {
+impl<T, A, L> Default for GrowLock<T, A, L>
+where
+    A: Allocator + Default,
+    L: WriteLock,
+{
     #[inline]
     fn default() -> Self {
-        Self::with_capacity_in(0, A::default())
+        GrowLock::with_capacity_in(0, A::default()).with_lock()
+    }
+}
+impl<T, A, L> Clone for GrowLock<T, A, L>
+where
+    T: Clone,
+    A: Allocator + Clone,
+    L: WriteLock,
+{
+    /// Clones the elements into a new [`GrowLock`], with the same
+    /// capacity. The clone is not poisoned, but it keeps
+    /// [`GrowLock::without_poisoning`].
+    ///
+    /// Elements pushed concurrently may or may not be cloned.
+    fn clone(&self) -> Self {
+        let mut lock = GrowLock::with_capacity_in(
+            self.capacity(),
+            self.allocator().clone(),
+        )
+        .with_lock();
+        lock.extend(self.iter().cloned());
+        lock.write_lock = self.write_lock.with_raw();
+        lock.clear_poison();
+        lock
+    }
+}
+
+// --------------------------- Iterator impl ---------------------------
+
+impl<T> FromIterator<T> for GrowLock<T> {
+    /// Collects the elements into a new [`GrowLock`].
+    ///
+    /// The elements are collected like into a [`Vec`]: the buffer is
+    /// allocated from the lower bound of the [`size_hint`] of the iterator,
+    /// and grows as needed, so the capacity is at least the number of
+    /// elements.
+    ///
+    /// [`size_hint`]: Iterator::size_hint
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        iter.into_iter().collect::<Vec<_>>().into()
+    }
+}
+impl<T, A: Allocator, L: WriteLock> Extend<T> for GrowLock<T, A, L> {
+    /// Extends the [`GrowLock<T>`] with the contents of an iterator.
+    ///
+    /// This ignores the poisoning of the lock.
+    ///
+    /// # Panics
+    /// This panics if the iterator has more elements than
+    /// `self.capacity() - self.len()` (i.e. pushing all the
+    /// elements would overflow `self.capacity()`.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(iter);
+    }
+}
+impl<'a, T, A, L> Extend<&'a T> for GrowLock<T, A, L>
+where
+    T: Copy + 'a,
+    A: Allocator,
+    L: WriteLock,
+{
+    /// Extends the [`GrowLock<T>`] with the copied contents of an
+    /// iterator.
+    ///
+    /// This ignores the poisoning of the lock.
+    ///
+    /// # Panics
+    /// This panics if the iterator has more elements than
+    /// `self.capacity() - self.len()` (i.e. pushing all the
+    /// elements would overflow `self.capacity()`.
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
     }
 }
 
 // ------------------------------- fmt impl -------------------------------
 
-impl<T: fmt::Debug, A: Allocator> fmt::Debug for GrowLock<T, A> {
+impl<T: fmt::Debug, A: Allocator, L: WriteLock> fmt::Debug
+    for GrowLock<T, A, L>
+{
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt::Debug::fmt(&**self, f)
@@ -690,6 +1788,7 @@ impl<T: fmt::Debug, A: Allocator> fmt::Debug for GrowLock<T, A> {
 
 // ----------------------------- From impl -----------------------------
 
+#[cfg(feature = "allocator_api")]
 impl<T, A: Allocator> From<Vec<T, A>> for GrowLock<T, A> {
     #[inline]
     fn from(value: Vec<T, A>) -> Self {
@@ -699,123 +1798,196 @@ impl<T, A: Allocator> From<Vec<T, A>> for GrowLock<T, A> {
         unsafe { Self::from_parts_in(ptr, len, cap, alloc) }
     }
 }
-impl<T, A: Allocator> From<GrowLock<T, A>> for Vec<T, A> {
+#[cfg(not(feature = "allocator_api"))]
+impl<T> From<Vec<T>> for GrowLock<T> {
+    #[inline]
+    fn from(value: Vec<T>) -> Self {
+        let mut vec = ManuallyDrop::new(value);
+        let (ptr, len, cap) =
+            (vec.as_mut_ptr(), vec.len(), vec.capacity());
+        // SAFETY: the `GrowLock` is constructed from parts of the given
+        // `Vec`, allocated by the global allocator.
+        unsafe { Self::from_raw_parts(ptr, len, cap) }
+    }
+}
+#[cfg(feature = "allocator_api")]
+impl<T, A: Allocator, L: WriteLock> From<GrowLock<T, A, L>> for Vec<T, A> {
     #[inline]
-    fn from(value: GrowLock<T, A>) -> Self {
+    fn from(value: GrowLock<T, A, L>) -> Self {
         let (ptr, len, cap, alloc) = value.into_parts_with_alloc();
         // SAFETY: the `Vec` is constructed from parts of the given
         // `GrowLock` so this is safe.
         unsafe { Self::from_parts_in(ptr, len, cap, alloc) }
     }
 }
+#[cfg(not(feature = "allocator_api"))]
+impl<T, L: WriteLock> From<GrowLock<T, Global, L>> for Vec<T> {
+    #[inline]
+    fn from(value: GrowLock<T, Global, L>) -> Self {
+        let (ptr, len, cap) = value.into_raw_parts();
+        // SAFETY: the `Vec` is constructed from parts of the given
+        // `GrowLock`, allocated by the global allocator.
+        unsafe { Self::from_raw_parts(ptr, len, cap) }
+    }
+}
+
+impl<T> From<Box<[T]>> for GrowLock<T> {
+    /// Creates a full [`GrowLock`], see [`GrowLock::from_boxed_slice`].
+    #[inline]
+    fn from(value: Box<[T]>) -> Self {
+        Self::from_boxed_slice(value)
+    }
+}
+impl<T, L: WriteLock> From<GrowLock<T, Global, L>> for Box<[T]> {
+    #[inline]
+    fn from(value: GrowLock<T, Global, L>) -> Self {
+        value.into_boxed_slice()
+    }
+}
+impl<T, L: WriteLock> From<GrowLock<T, Global, L>> for Arc<[T]> {
+    /// Moves the elements into a new [`Arc<[T]>`](Arc).
+    ///
+    /// An [`Arc`] stores its reference counts before its elements, so it
+    /// can't reuse the buffer: the elements are moved (i.e. copied
+    /// bitwise, without cloning them) once. See [`GrowLock::freeze`]
+    /// to keep the buffer instead.
+    #[inline]
+    fn from(value: GrowLock<T, Global, L>) -> Self {
+        Vec::from(value).into()
+    }
+}
 
 // -------------------------- PartialEq impl ---------------------------
 
-impl<T, U, A, A2> PartialEq<GrowLock<U, A2>> for GrowLock<T, A>
+impl<T, U, A, A2, L, L2> PartialEq<GrowLock<U, A2, L2>>
+    for GrowLock<T, A, L>
 where
     T: PartialEq<U>,
     A: Allocator,
     A2: Allocator,
+    L: WriteLock,
+    L2: WriteLock,
 {
     #[inline]
-    fn eq(&self, rhs: &GrowLock<U, A2>) -> bool {
+    fn eq(&self, rhs: &GrowLock<U, A2, L2>) -> bool {
         PartialEq::eq(&**self, &**rhs)
     }
 }
-impl<T, U, A> PartialEq<[U]> for GrowLock<T, A>
+impl<T, U, A, L> PartialEq<[U]> for GrowLock<T, A, L>
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
     #[inline]
     fn eq(&self, rhs: &[U]) -> bool {
         PartialEq::eq(&**self, rhs)
     }
 }
-impl<T, U, A> PartialEq<GrowLock<U, A>> for [T]
+impl<T, U, A, L> PartialEq<GrowLock<U, A, L>> for [T]
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
-    fn eq(&self, rhs: &GrowLock<U, A>) -> bool {
+    fn eq(&self, rhs: &GrowLock<U, A, L>) -> bool {
         PartialEq::eq(self, &**rhs)
     }
 }
-impl<T, U, A> PartialEq<&[U]> for GrowLock<T, A>
+impl<T, U, A, L> PartialEq<&[U]> for GrowLock<T, A, L>
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
     #[inline]
     fn eq(&self, rhs: &&[U]) -> bool {
         PartialEq::eq(&**self, *rhs)
     }
 }
-impl<T, U, A> PartialEq<GrowLock<U, A>> for &[T]
+impl<T, U, A, L> PartialEq<GrowLock<U, A, L>> for &[T]
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
-    fn eq(&self, rhs: &GrowLock<U, A>) -> bool {
+    fn eq(&self, rhs: &GrowLock<U, A, L>) -> bool {
         PartialEq::eq(*self, &**rhs)
     }
 }
-impl<T, U, A> PartialEq<&mut [U]> for GrowLock<T, A>
+impl<T, U, A, L> PartialEq<&mut [U]> for GrowLock<T, A, L>
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
     #[inline]
     fn eq(&self, rhs: &&mut [U]) -> bool {
         PartialEq::eq(&**self, *rhs)
     }
 }
-impl<T, U, A> PartialEq<GrowLock<U, A>> for &mut [T]
+impl<T, U, A, L> PartialEq<GrowLock<U, A, L>> for &mut [T]
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
-    fn eq(&self, rhs: &GrowLock<U, A>) -> bool {
+    fn eq(&self, rhs: &GrowLock<U, A, L>) -> bool {
         PartialEq::eq(*self, &**rhs)
     }
 }
-impl<T, U, A, const N: usize> PartialEq<[U; N]> for GrowLock<T, A>
+impl<T, U, A, L, const N: usize> PartialEq<[U; N]> for GrowLock<T, A, L>
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
     #[inline]
     fn eq(&self, rhs: &[U; N]) -> bool {
         PartialEq::eq(&**self, rhs)
     }
 }
-impl<T, U, A, const N: usize> PartialEq<GrowLock<U, A>> for [T; N]
+impl<T, U, A, L, const N: usize> PartialEq<GrowLock<U, A, L>> for [T; N]
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
 {
-    fn eq(&self, rhs: &GrowLock<U, A>) -> bool {
+    fn eq(&self, rhs: &GrowLock<U, A, L>) -> bool {
         PartialEq::eq(self, &**rhs)
     }
 }
-impl<T, U, A, A2> PartialEq<Vec<U, A2>> for GrowLock<T, A>
+#[cfg(feature = "allocator_api")]
+impl<T, U, A, A2, L> PartialEq<Vec<U, A2>> for GrowLock<T, A, L>
 where
     T: PartialEq<U>,
     A: Allocator,
+    L: WriteLock,
     A2: Allocator,
 {
     fn eq(&self, rhs: &Vec<U, A2>) -> bool {
         PartialEq::eq(&**self, &**rhs)
     }
 }
+#[cfg(not(feature = "allocator_api"))]
+impl<T, U, A, L> PartialEq<Vec<U>> for GrowLock<T, A, L>
+where
+    T: PartialEq<U>,
+    A: Allocator,
+    L: WriteLock,
+{
+    fn eq(&self, rhs: &Vec<U>) -> bool {
+        PartialEq::eq(&**self, &**rhs)
+    }
+}
 
 // ----------------------------- Eq and Hash impl
 // -----------------------------
 
-impl<T: Eq, A: Allocator> Eq for GrowLock<T, A> {}
+impl<T: Eq, A: Allocator, L: WriteLock> Eq for GrowLock<T, A, L> {}
 /// [`GrowLock`] implements [`Borrow<[T]>`], so we need to `hash` the
 /// same way as the slice does.
-impl<T: Hash, A: Allocator> Hash for GrowLock<T, A> {
+impl<T: Hash, A: Allocator, L: WriteLock> Hash for GrowLock<T, A, L> {
     /// [`GrowLock`] implements [`Borrow<[T]>`], so we need to `hash` the
     /// same way as the slice does.
     #[inline]
@@ -0,0 +1,323 @@
+//! Backends of the write lock of a [`GrowLock`](crate::GrowLock).
+//!
+//! A [`GrowLock`](crate::GrowLock) is generic over its write lock, which
+//! only serializes the writers (the readers never take it). The default
+//! ([`DefaultLock`]) is `StdLock` with the `std` feature, which puts the
+//! waiting writers to sleep, and `SpinLock` without it. Other backends are
+//! available behind features:
+//! * `spin`: `SpinLock`, which never sleeps.
+//! * `ticket`: `TicketLock`, which hands the lock over in FIFO order.
+//! * `futex`: `FutexLock`, which sleeps on a Linux futex.
+//!
+//! `FutexLock` only exists on Linux: on the other targets, the `futex`
+//! feature is accepted (so `--all-features` still builds) but doesn't
+//! provide any backend.
+//!
+//! The backend is chosen with [`GrowLock::with_lock`]:
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use growlock::{GrowLock, lock::StdLock};
+//!
+//! let lock = GrowLock::with_capacity(5).with_lock::<StdLock>();
+//! lock.write().unwrap().push(1);
+//! # }
+//! ```
+//!
+//! Poisoning is handled by the [`GrowLock`](crate::GrowLock) itself, so it
+//! works the same with every backend (see
+//! [`GrowLock::without_poisoning`](crate::GrowLock::without_poisoning)).
+//!
+//! [`GrowLock::with_lock`]: crate::GrowLock::with_lock
+
+#[cfg(not(loom))]
+use core::sync::atomic::{AtomicBool, Ordering};
+#[cfg(all(feature = "std", not(loom)))]
+use std::sync::{
+    Condvar, Mutex,
+    atomic::{self, AtomicUsize},
+};
+#[cfg(feature = "std")]
+use std::{sync::PoisonError, thread, time::Instant};
+
+#[cfg(loom)]
+use loom::sync::{
+    Condvar, Mutex,
+    atomic::{self, AtomicBool, AtomicUsize, Ordering},
+};
+
+#[cfg(all(feature = "futex", target_os = "linux", not(loom)))]
+mod futex;
+#[cfg(any(feature = "spin", not(feature = "std")))]
+mod spin;
+#[cfg(feature = "ticket")]
+mod ticket;
+
+#[cfg(all(feature = "futex", target_os = "linux", not(loom)))]
+pub use futex::FutexLock;
+#[cfg(any(feature = "spin", not(feature = "std")))]
+pub use spin::SpinLock;
+#[cfg(feature = "ticket")]
+pub use ticket::TicketLock;
+
+/// The write lock of a [`GrowLock`](crate::GrowLock) when none is given:
+/// `StdLock` with the `std` feature, `SpinLock` without it.
+#[cfg(feature = "std")]
+pub type DefaultLock = StdLock;
+/// The write lock of a [`GrowLock`](crate::GrowLock) when none is given:
+/// `StdLock` with the `std` feature, `SpinLock` without it.
+#[cfg(not(feature = "std"))]
+pub type DefaultLock = SpinLock;
+
+/// A raw lock, giving exclusive access to the writers of a
+/// [`GrowLock`](crate::GrowLock).
+///
+/// [`Default::default`] must return an unlocked lock.
+///
+/// # Safety
+/// * after [`try_lock`](WriteLock::try_lock) or `try_lock_until` return
+///   `true`, or after [`lock`](WriteLock::lock) returns, no other thread
+///   may acquire the lock until [`unlock`](WriteLock::unlock) is called.
+/// * acquiring the lock must synchronize with the previous `unlock` (i.e.
+///   with an [`Ordering::Acquire`] and an [`Ordering::Release`]), so the
+///   writes of the previous holder are visible.
+pub unsafe trait WriteLock: Default + Send + Sync {
+    /// Acquires the lock if it is free, without blocking.
+    fn try_lock(&self) -> bool;
+    /// Acquires the lock, blocking the current thread until it is free.
+    fn lock(&self);
+    /// Acquires the lock, blocking the current thread until it is free or
+    /// `deadline` is reached. Returns `false` in the latter case.
+    #[cfg(feature = "std")]
+    fn try_lock_until(&self, deadline: Instant) -> bool;
+    /// Releases the lock.
+    ///
+    /// # Safety
+    /// The lock must be held by the caller (although not necessarily on
+    /// the same thread it was acquired on).
+    unsafe fn unlock(&self);
+}
+
+/// The default write lock, which puts the waiting writers to sleep on a
+/// [`Condvar`].
+///
+/// The lock is not fair: a writer coming in may take it before the
+/// sleeping ones.
+///
+/// # Protocol
+/// The lock is taken with a compare-exchange on `locked`. A thread which
+/// has to block registers itself in `sleepers`, and then retries while
+/// holding the `mutex`, before sleeping on the `condvar`. Unlocking
+/// clears `locked`, and then checks `sleepers`: only if someone is
+/// sleeping it locks the `mutex` and wakes them up.
+///
+/// This is the "store buffering" pattern: each side issues a `SeqCst`
+/// fence between its write and its read, so at least one of them observes
+/// the write of the other.
+///
+/// This is only available with the `std` feature.
+#[cfg(feature = "std")]
+pub struct StdLock {
+    locked: AtomicBool,
+    /// Number of sleeping threads.
+    sleepers: AtomicUsize,
+    mutex: Mutex<()>,
+    condvar: Condvar,
+}
+
+#[cfg(feature = "std")]
+impl StdLock {
+    #[cold]
+    fn lock_slow(&self, deadline: Option<Instant>) -> bool {
+        self.sleepers.fetch_add(1, Ordering::Relaxed);
+        atomic::fence(Ordering::SeqCst);
+        // this mutex only protects the sleep, so poisoning is irrelevant.
+        let mut guard =
+            self.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        let locked = loop {
+            if self.try_lock() {
+                break true;
+            }
+            match deadline {
+                None => {
+                    guard = self
+                        .condvar
+                        .wait(guard)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break false;
+                    }
+                    guard = self
+                        .condvar
+                        .wait_timeout(guard, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            }
+        };
+        drop(guard);
+        self.sleepers.fetch_sub(1, Ordering::Relaxed);
+        locked
+    }
+    #[cold]
+    fn unlock_slow(&self) {
+        // a sleeper may have failed to take the lock before our store but
+        // not be sleeping yet: taking the mutex waits for it to sleep.
+        drop(self.mutex.lock());
+        // all of them are woken up, as the ones with a deadline may leave
+        // without taking the lock.
+        self.condvar.notify_all();
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdLock {
+    fn default() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            sleepers: AtomicUsize::new(0),
+            mutex: Mutex::new(()),
+            condvar: Condvar::new(),
+        }
+    }
+}
+
+// SAFETY: the lock is taken with a compare-exchange from `false` to
+// `true` (Acquire), and released by storing `false` (Release).
+#[cfg(feature = "std")]
+unsafe impl WriteLock for StdLock {
+    #[inline]
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(
+                false,
+                true,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+    #[inline]
+    fn lock(&self) {
+        if !self.try_lock() {
+            self.lock_slow(None);
+        }
+    }
+    #[inline]
+    fn try_lock_until(&self, deadline: Instant) -> bool {
+        self.try_lock() || self.lock_slow(Some(deadline))
+    }
+    #[inline]
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+        atomic::fence(Ordering::SeqCst);
+        if self.sleepers.load(Ordering::Relaxed) != 0 {
+            self.unlock_slow();
+        }
+    }
+}
+
+/// Write lock of a [`GrowLock`](crate::GrowLock), with poisoning.
+pub(crate) struct LockState<L> {
+    raw: L,
+    /// Raised when a writer panics while holding the lock.
+    poisoned: AtomicBool,
+    /// Whether a panicking writer poisons the lock.
+    poisoning: bool,
+}
+
+/// Proof of the write access, returned by the locking methods of
+/// [`LockState`].
+#[must_use]
+pub(crate) struct WritePermit {
+    /// Whether the thread was already panicking when it took the lock.
+    panicking: bool,
+}
+
+impl<L: WriteLock> LockState<L> {
+    pub(crate) fn new() -> Self {
+        Self {
+            raw: L::default(),
+            poisoned: AtomicBool::new(false),
+            poisoning: true,
+        }
+    }
+    /// Returns a new unlocked state with another raw lock, keeping the
+    /// poisoning of `self`.
+    pub(crate) fn with_raw<L2: WriteLock>(&self) -> LockState<L2> {
+        LockState {
+            raw: L2::default(),
+            poisoned: AtomicBool::new(self.is_poisoned()),
+            poisoning: self.poisoning,
+        }
+    }
+    /// Disables the poisoning, clearing it.
+    pub(crate) fn disable_poisoning(&mut self) {
+        self.poisoning = false;
+        self.clear_poison();
+    }
+
+    /// Takes the lock if it is free.
+    #[inline]
+    pub(crate) fn try_lock(&self) -> Option<WritePermit> {
+        self.raw.try_lock().then(Self::permit)
+    }
+    /// Takes the lock, blocking until it is free.
+    #[inline]
+    pub(crate) fn lock(&self) -> WritePermit {
+        self.raw.lock();
+        Self::permit()
+    }
+    /// Takes the lock, blocking until it is free or `deadline` is
+    /// reached.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn try_lock_until(
+        &self,
+        deadline: Instant,
+    ) -> Option<WritePermit> {
+        self.raw.try_lock_until(deadline).then(Self::permit)
+    }
+    fn permit() -> WritePermit {
+        WritePermit {
+            panicking: panicking(),
+        }
+    }
+    /// Returns `true` if a writer panicked while holding the lock.
+    #[inline]
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub(crate) fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Relaxed);
+    }
+
+    /// Releases the lock, poisoning it (unless disabled) if the thread
+    /// started panicking since it took the lock.
+    ///
+    /// # Safety
+    /// `permit` must come from this lock, and it must not be used after
+    /// this call.
+    #[inline]
+    pub(crate) unsafe fn unlock(&self, permit: &WritePermit) {
+        if self.poisoning && !permit.panicking && panicking() {
+            // ordered by the release of the lock.
+            self.poisoned.store(true, Ordering::Relaxed);
+        }
+        // SAFETY: the caller holds the lock.
+        unsafe { self.raw.unlock() };
+    }
+}
+
+/// Whether the current thread is panicking. Without `std` a panic can't be
+/// detected, so the lock is never poisoned.
+#[inline]
+fn panicking() -> bool {
+    #[cfg(feature = "std")]
+    return thread::panicking();
+    #[cfg(not(feature = "std"))]
+    return false;
+}
@@ -0,0 +1,131 @@
+//! Futex lock backend (Linux only).
+
+use {
+    super::WriteLock,
+    std::{
+        ptr,
+        sync::atomic::{AtomicU32, Ordering},
+        time::Instant,
+    },
+};
+
+/// The lock is free.
+const UNLOCKED: u32 = 0;
+/// The lock is held, and no one sleeps on it.
+const LOCKED: u32 = 1;
+/// The lock is held, and someone may sleep on it.
+const CONTENDED: u32 = 2;
+
+/// A write lock which sleeps on a Linux futex.
+///
+/// Unlike [`StdLock`](super::StdLock), this is a single 32-bit word: an
+/// uncontended unlock is a single atomic swap, and the waiting writers
+/// sleep in the kernel directly. The lock is not fair.
+pub struct FutexLock {
+    state: AtomicU32,
+}
+
+impl FutexLock {
+    #[cold]
+    fn lock_contended(&self, deadline: Option<Instant>) -> bool {
+        loop {
+            // marking the lock as contended before sleeping makes sure the
+            // holder wakes us up.
+            if self.state.swap(CONTENDED, Ordering::Acquire) == UNLOCKED {
+                return true;
+            }
+            let timeout = match deadline {
+                None => None,
+                Some(deadline) => {
+                    let Some(timeout) =
+                        deadline.checked_duration_since(Instant::now())
+                    else {
+                        // we may have consumed a wake up meant for another
+                        // sleeper: pass it on.
+                        self.wake_one();
+                        return false;
+                    };
+                    // `time_t` and the type of `tv_nsec` are 32 bits wide
+                    // on some targets, where the conversions are fallible.
+                    // The nanoseconds are always below 1e9, so they fit.
+                    #[allow(clippy::unnecessary_fallible_conversions)]
+                    let timespec = libc::timespec {
+                        tv_sec: timeout
+                            .as_secs()
+                            .try_into()
+                            .unwrap_or(libc::time_t::MAX),
+                        tv_nsec: timeout
+                            .subsec_nanos()
+                            .try_into()
+                            .unwrap_or(999_999_999),
+                    };
+                    Some(timespec)
+                }
+            };
+            // SAFETY: `state` is a valid futex word, and `timeout` (if
+            // any) outlives the call. The kernel returns immediately if
+            // the lock is not contended anymore.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    self.state.as_ptr(),
+                    libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                    CONTENDED,
+                    timeout.as_ref().map_or(ptr::null(), ptr::from_ref),
+                );
+            }
+        }
+    }
+    #[cold]
+    fn wake_one(&self) {
+        // SAFETY: `state` is a valid futex word.
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                self.state.as_ptr(),
+                libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                1,
+            );
+        }
+    }
+}
+
+impl Default for FutexLock {
+    fn default() -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+        }
+    }
+}
+
+// SAFETY: the lock is taken by moving `state` away from `UNLOCKED`
+// (Acquire), and released by storing `UNLOCKED` back (Release).
+unsafe impl WriteLock for FutexLock {
+    #[inline]
+    fn try_lock(&self) -> bool {
+        self.state
+            .compare_exchange(
+                UNLOCKED,
+                LOCKED,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+    #[inline]
+    fn lock(&self) {
+        if !self.try_lock() {
+            self.lock_contended(None);
+        }
+    }
+    #[inline]
+    fn try_lock_until(&self, deadline: Instant) -> bool {
+        self.try_lock() || self.lock_contended(Some(deadline))
+    }
+    #[inline]
+    unsafe fn unlock(&self) {
+        if self.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            self.wake_one();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Spin lock backend.
+
+#[cfg(not(loom))]
+use core::{
+    hint,
+    sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(loom)]
+use loom::{
+    hint,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::WriteLock;
+
+/// A write lock which busy-waits instead of sleeping.
+///
+/// This has the lowest handoff latency when the writers hold the lock for
+/// a short time, at the cost of burning the CPU while waiting. The lock is
+/// not fair.
+pub struct SpinLock {
+    locked: AtomicBool,
+}
+
+impl Default for SpinLock {
+    fn default() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+}
+
+// SAFETY: the lock is taken with a compare-exchange from `false` to
+// `true` (Acquire), and released by storing `false` (Release).
+unsafe impl WriteLock for SpinLock {
+    #[inline]
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(
+                false,
+                true,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+    #[inline]
+    fn lock(&self) {
+        while !self.try_lock() {
+            // spins on a load, so the cache line isn't written while the
+            // lock is held.
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+    }
+    #[cfg(feature = "std")]
+    fn try_lock_until(&self, deadline: Instant) -> bool {
+        while !self.try_lock() {
+            while self.locked.load(Ordering::Relaxed) {
+                if Instant::now() >= deadline {
+                    return false;
+                }
+                hint::spin_loop();
+            }
+        }
+        true
+    }
+    #[inline]
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,85 @@
+//! Ticket lock backend.
+
+#[cfg(not(loom))]
+use core::{
+    hint,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+#[cfg(loom)]
+use loom::{
+    hint,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use super::WriteLock;
+
+/// A fair write lock, which hands the lock over to the blocked writers in
+/// the order they arrived.
+///
+/// Each writer takes a ticket, and busy-waits until its ticket is served.
+/// A writer with a deadline (`GrowLock::try_write_until`, with the `std`
+/// feature) can't give its ticket back, so it doesn't take one: it only
+/// gets the lock when it is free and no one is queued.
+pub struct TicketLock {
+    /// Next ticket to hand out.
+    next: AtomicUsize,
+    /// Ticket holding the lock, or the next one to hold it if the lock is
+    /// free.
+    serving: AtomicUsize,
+}
+
+impl Default for TicketLock {
+    fn default() -> Self {
+        Self {
+            next: AtomicUsize::new(0),
+            serving: AtomicUsize::new(0),
+        }
+    }
+}
+
+// SAFETY: a ticket is held by a single writer, and only the writer whose
+// ticket is `serving` holds the lock. Taking the lock loads `serving` with
+// Acquire, and releasing it stores the next ticket with Release.
+unsafe impl WriteLock for TicketLock {
+    #[inline]
+    fn try_lock(&self) -> bool {
+        let serving = self.serving.load(Ordering::Acquire);
+        // the lock is free only if no one holds the ticket `serving`, in
+        // which case we take it.
+        self.next
+            .compare_exchange(
+                serving,
+                serving.wrapping_add(1),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    }
+    #[inline]
+    fn lock(&self) {
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        while self.serving.load(Ordering::Acquire) != ticket {
+            hint::spin_loop();
+        }
+    }
+    #[cfg(feature = "std")]
+    fn try_lock_until(&self, deadline: Instant) -> bool {
+        while !self.try_lock() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            hint::spin_loop();
+        }
+        true
+    }
+    #[inline]
+    unsafe fn unlock(&self) {
+        // only the holder of the lock stores `serving`.
+        let serving = self.serving.load(Ordering::Relaxed);
+        self.serving
+            .store(serving.wrapping_add(1), Ordering::Release);
+    }
+}
@@ -23,7 +23,7 @@ macro_rules! grow_lock {
         {
             let mut __guard__ = __v__.write().unwrap();
             for _ in 0 .. $len {
-                __guard__.push(::std::clone::Clone::clone(&$elem));
+                __guard__.push(::core::clone::Clone::clone(&$elem));
             }
         }
         __v__
@@ -33,7 +33,7 @@ macro_rules! grow_lock {
         {
             let mut __guard__ = __v__.write().unwrap();
             for _ in 0 .. $len {
-                __guard__.push(::std::clone::Clone::clone(&$elem));
+                __guard__.push(::core::clone::Clone::clone(&$elem));
             }
         }
         __v__
@@ -41,6 +41,6 @@ macro_rules! grow_lock {
 
     // this is last because everything can match this
     ($($elem:expr),+$(,)?) => {{
-        $crate::GrowLock::from(::std::vec![$($elem),*])
+        $crate::GrowLock::from($crate::__private::vec![$($elem),*])
     }};
 }
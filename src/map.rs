@@ -0,0 +1,281 @@
+//! An append-only hash map built on a [`GrowLock`].
+//!
+//! [`GrowMap`] stores its entries in a [`GrowLock`], and looks them up
+//! through an open-addressing hash index. Entries are never removed nor
+//! modified after their insertion, so readers can probe the index and
+//! read the entries without locking. Inserts are serialized by the write
+//! lock of the [`GrowLock`].
+//!
+//! [`GrowLock`]: crate::GrowLock
+
+use {
+    crate::{error::LengthError, index::IndexedLock},
+    std::{
+        borrow::Borrow,
+        fmt,
+        hash::{BuildHasher, Hash, RandomState},
+        iter::FusedIterator,
+        ops, slice,
+    },
+};
+
+/// An append-only hash map, whose entries can't be removed nor modified
+/// once inserted.
+///
+/// # Examples
+/// ```
+/// use growlock::map::GrowMap;
+///
+/// let map = GrowMap::with_capacity(10);
+/// let one = map.get_or_insert("one", 1);
+/// assert_eq!(*one, 1);
+/// // the entry is already there, so the new value is dropped.
+/// assert_eq!(*map.get_or_insert("one", 2), 1);
+///
+/// assert_eq!(map.get("one"), Some(&1));
+/// assert_eq!(map.get("two"), None);
+/// ```
+pub struct GrowMap<K, V, S = RandomState> {
+    entries: IndexedLock<(K, V)>,
+    hasher: S,
+}
+
+impl<K, V> GrowMap<K, V> {
+    /// Creates an empty [`GrowMap`] which can hold up to `capacity`
+    /// entries.
+    ///
+    /// # Panics
+    /// Panics if the capacity exceeds `isize::MAX` _bytes_.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> GrowMap<K, V, S> {
+    /// Creates an empty [`GrowMap`] which can hold up to `capacity`
+    /// entries, using `hasher` to hash the keys.
+    ///
+    /// # Panics
+    /// Panics if the capacity exceeds `isize::MAX` _bytes_.
+    #[inline]
+    #[must_use]
+    pub fn with_capacity_and_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            entries: IndexedLock::with_capacity(capacity),
+            hasher,
+        }
+    }
+    /// Returns the number of entries.
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the maximum number of entries.
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn hasher(&self) -> &S {
+        &self.hasher
+    }
+    /// Returns an iterator over the entries, in insertion order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter(self.entries.iter())
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> GrowMap<K, V, S> {
+    /// Returns the value of `key`, if any.
+    #[inline]
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+    /// Returns the entry of `key`, if any.
+    #[inline]
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, value) = &self.entries[self.find(key)?];
+        Some((key, value))
+    }
+    #[inline]
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.find(key).is_some()
+    }
+    /// Returns the value of `key`, inserting `value` if there's none.
+    ///
+    /// # Panics
+    /// Panics if `key` is not in the map and the map is full.
+    #[inline]
+    pub fn get_or_insert(&self, key: K, value: V) -> &V {
+        self.get_or_insert_with(key, || value)
+    }
+    /// Returns the value of `key`, inserting the result of `f` if there's
+    /// none.
+    ///
+    /// `f` is called while holding the write lock of the map, so it should
+    /// be quick.
+    ///
+    /// # Panics
+    /// Panics if `key` is not in the map and the map is full.
+    #[inline]
+    pub fn get_or_insert_with(&self, key: K, f: impl FnOnce() -> V) -> &V {
+        self.try_get_or_insert_with(key, f).expect("map is full")
+    }
+    /// Returns the value of `key`, inserting the result of `f` if there's
+    /// none.
+    ///
+    /// `f` is called while holding the write lock of the map, so it should
+    /// be quick.
+    ///
+    /// # Errors
+    /// Returns an error if `key` is not in the map and the map is full.
+    pub fn try_get_or_insert_with(
+        &self,
+        key: K,
+        f: impl FnOnce() -> V,
+    ) -> Result<&V, LengthError> {
+        let hash = self.hasher.hash_one(&key);
+        let id = self.entries.get_or_push(
+            hash,
+            (key, f),
+            |(k, _), (key, _)| k == key,
+            |(key, f)| (key, f()),
+        )?;
+        Ok(&self.entries[id].1)
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        self.entries.find(hash, |(k, _)| k.borrow() == key)
+    }
+}
+
+impl<K, V, Q, S> ops::Index<&Q> for GrowMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+    /// Returns the value of `key`.
+    ///
+    /// # Panics
+    /// Panics if `key` is not in the map.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not found")
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a GrowMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for GrowMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the entries of a [`GrowMap`], in insertion order.
+///
+/// This structure is created by the [`iter`](GrowMap::iter) method on
+/// [`GrowMap`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a, K, V>(slice::Iter<'a, (K, V)>);
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key, value))
+    }
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|(key, value)| (key, value))
+    }
+}
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_or_insert() {
+        let map = GrowMap::with_capacity(2);
+        assert_eq!(*map.get_or_insert(String::from("a"), 1), 1);
+        assert_eq!(*map.get_or_insert_with(String::from("a"), || 2), 1);
+        assert_eq!(*map.get_or_insert_with(String::from("b"), || 2), 2);
+        assert_eq!(
+            map.try_get_or_insert_with(String::from("c"), || 3),
+            Err(LengthError)
+        );
+        assert_eq!(
+            map.try_get_or_insert_with(String::from("b"), || 3),
+            Ok(&2)
+        );
+
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get_key_value("b"), Some((&String::from("b"), &2)));
+        assert!(!map.contains_key("c"));
+        assert_eq!(map["b"], 2);
+        assert_eq!(
+            map.iter().collect::<Vec<_>>(),
+            [(&String::from("a"), &1), (&String::from("b"), &2)]
+        );
+    }
+
+    /// A panicking `f` doesn't insert anything
+    #[test]
+    fn get_or_insert_panic() {
+        use std::panic::{self, AssertUnwindSafe};
+
+        let map = GrowMap::with_capacity(2);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            map.get_or_insert_with(1, || panic!("oops!"));
+        }));
+        assert!(result.is_err());
+        assert!(map.is_empty());
+        assert_eq!(*map.get_or_insert(1, 'a'), 'a');
+    }
+}
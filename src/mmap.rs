@@ -0,0 +1,996 @@
+//! An append-only array persisted in a memory-mapped file.
+//!
+//! A [`MmapGrowLock`] works like a [`GrowLock`](crate::GrowLock) of
+//! [`Pod`] elements, whose buffer is a shared mapping of a file: the
+//! readers never lock, and the writers push through a [`MmapGuard`].
+//! Reopening the file restores the elements flushed by the previous
+//! process.
+//!
+//! This module is only available with the `mmap` feature, on Unix.
+//!
+//! # File layout
+//! The file starts with a header holding a magic number, the size and the
+//! alignment of `T`, the capacity and the _durable_ length, followed by
+//! the buffer of `capacity` elements. The integers and the elements are
+//! stored in the native byte order, so the file is not portable across
+//! architectures.
+//!
+//! # Durability
+//! A pushed element is published to the readers of the process at once,
+//! but it is only persisted once flushed, with [`MmapGuard::flush`] or
+//! when the guard is dropped. Flushing syncs the new elements to the file
+//! (with `msync`) _before_ storing the new durable length in the header
+//! and syncing it, so the durable length never covers an element which
+//! didn't reach the file.
+//!
+//! # Recovery
+//! When a file is opened, after a crash or not:
+//! * the elements past the durable length (pushed but not flushed, and
+//!   possibly torn) are discarded, and overwritten by the next pushes.
+//! * a durable length greater than the capacity is clamped to it.
+//! * a file shorter than its capacity (e.g. truncated) is extended with
+//!   zeroes, and the durable length is clamped to the elements it held.
+//! * a clamped durable length is stored back in the header, so the
+//!   discarded slots are only restored once they are flushed again.
+//! * a header with another magic number, version, or size and alignment of
+//!   `T` is rejected with [`io::ErrorKind::InvalidData`].
+//!
+//! A file is opened by a single [`MmapGrowLock`] at a time (it is locked
+//! with `flock`): another one fails with [`io::ErrorKind::WouldBlock`].
+//!
+//! # Safety
+//! The readers borrow the elements straight from the mapping of the
+//! file, but the `flock` is only advisory: anyone writing or truncating
+//! the file behind the [`MmapGrowLock`] (another process, or this one
+//! through [`fs::write`]) changes elements under a shared reference, or
+//! makes reading them fault with `SIGBUS`. This is why the constructors
+//! are `unsafe`: while the [`MmapGrowLock`] is alive, the file must only
+//! be modified through it.
+//!
+//! # Examples
+//! ```
+//! use growlock::mmap::MmapGrowLock;
+//!
+//! let path = std::env::temp_dir().join("growlock-doc-example.log");
+//! # let _ = std::fs::remove_file(&path);
+//! // SAFETY: the file is only modified through the `MmapGrowLock`.
+//! let log = unsafe { MmapGrowLock::<u64>::create(&path, 100)? };
+//! log.write().unwrap().extend_from_slice(&[1, 2, 3]);
+//! drop(log);
+//!
+//! // SAFETY: as above.
+//! let log = unsafe { MmapGrowLock::<u64>::open(&path)? };
+//! assert_eq!(&log[..], &[1, 2, 3]);
+//! assert_eq!(log.capacity(), 100);
+//! # std::fs::remove_file(&path)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+use {
+    crate::{
+        error::{
+            LengthError, LockResult, PoisonError, TryLockError,
+            TryLockResult,
+        },
+        lock::{DefaultLock, LockState, WriteLock, WritePermit},
+    },
+    std::{
+        fmt,
+        fs::{self, File, OpenOptions},
+        io,
+        marker::PhantomData,
+        mem::{self, ManuallyDrop},
+        ops::{self, Range},
+        os::{fd::AsRawFd, unix::fs::FileExt},
+        path::{Path, PathBuf},
+        process,
+        ptr::{self, NonNull},
+        slice,
+        sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+
+/// Plain old data, which can be stored in a file and read back by another
+/// process.
+///
+/// # Safety
+/// * every bit pattern (including all zeroes) must be a valid `Self`, as
+///   the elements are read back from the file.
+/// * `Self` must not have padding bytes, as its bytes are written to the
+///   file.
+/// * `Self` must not hold pointers or references, which wouldn't be valid
+///   in another process.
+pub unsafe trait Pod: Copy + Send + Sync + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {$(
+        // SAFETY: a primitive number has no padding, and every bit
+        // pattern is valid.
+        unsafe impl Pod for $t {}
+    )*};
+}
+impl_pod!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32,
+    f64
+);
+// SAFETY: there is no padding between the elements of an array.
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+const MAGIC: [u8; 8] = *b"GROWLOCK";
+const VERSION: u32 = 1;
+
+/// Header at the start of the file.
+#[repr(C)]
+struct Header {
+    magic: [u8; 8],
+    version: u32,
+    elem_size: u32,
+    elem_align: u32,
+    _reserved: u32,
+    capacity: u64,
+    /// Number of elements flushed to the file.
+    len: AtomicU64,
+}
+
+impl Header {
+    /// Offset of `len` in the file.
+    const LEN_OFFSET: usize = mem::offset_of!(Self, len);
+
+    /// Reads the header of `file`, checking that it holds elements of type
+    /// `T`.
+    fn read<T>(file: &File) -> io::Result<(usize, usize)> {
+        let mut bytes = [0; size_of::<Self>()];
+        file.read_exact_at(&mut bytes, 0).map_err(|e| {
+            if e.kind() == io::ErrorKind::UnexpectedEof {
+                invalid_data("the file is too short for a header")
+            } else {
+                e
+            }
+        })?;
+        let u32_at = |i: usize| {
+            u32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap())
+        };
+        let u64_at = |i: usize| {
+            u64::from_ne_bytes(bytes[i..i + 8].try_into().unwrap())
+        };
+        let magic = mem::offset_of!(Self, magic);
+        if bytes[magic..magic + MAGIC.len()] != MAGIC {
+            return Err(invalid_data("not a `MmapGrowLock` file"));
+        }
+        if u32_at(mem::offset_of!(Self, version)) != VERSION {
+            return Err(invalid_data("unsupported version"));
+        }
+        if u32_at(mem::offset_of!(Self, elem_size)) as usize
+            != size_of::<T>()
+            || u32_at(mem::offset_of!(Self, elem_align)) as usize
+                != align_of::<T>()
+        {
+            return Err(invalid_data(
+                "the file holds elements of another type",
+            ));
+        }
+        let capacity =
+            usize::try_from(u64_at(mem::offset_of!(Self, capacity)))
+                .map_err(|_| invalid_data("the capacity is too large"))?;
+        let len = usize::try_from(u64_at(Self::LEN_OFFSET))
+            .unwrap_or(usize::MAX);
+        Ok((capacity, len))
+    }
+}
+
+/// Offset of the buffer of elements of type `T` in a file starting with a
+/// header `H`.
+pub(crate) const fn data_offset<H, T>() -> usize {
+    let align = if align_of::<T>() > align_of::<H>() {
+        align_of::<T>()
+    } else {
+        align_of::<H>()
+    };
+    size_of::<H>().next_multiple_of(align)
+}
+
+/// Size of a file starting with a header `H`, and holding `capacity`
+/// elements of type `T`.
+pub(crate) fn file_size<H, T>(capacity: usize) -> io::Result<usize> {
+    size_of::<T>()
+        .checked_mul(capacity)
+        .and_then(|size| size.checked_add(data_offset::<H, T>()))
+        .filter(|&size| isize::try_from(size).is_ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the capacity is too large",
+            )
+        })
+}
+
+pub(crate) fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// An append-only array of [`Pod`] elements persisted in a memory-mapped
+/// file, see the [module docs](self).
+pub struct MmapGrowLock<T: Pod, L: WriteLock = DefaultLock> {
+    /// Shared mapping of the whole file, header included.
+    map: NonNull<u8>,
+    map_len: usize,
+    capacity: usize,
+    /// Number of published elements, for the readers of this process.
+    ///
+    /// It follows the publication protocol of [`GrowLock`]'s `len`: it
+    /// is stored with [`Ordering::Release`] after the elements are
+    /// written.
+    ///
+    /// [`GrowLock`]: crate::GrowLock
+    len: AtomicUsize,
+    write_lock: LockState<L>,
+    /// Keeps the `flock` of the file.
+    file: File,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: the mapping is owned by the `MmapGrowLock`, like a `Box<[T]>`,
+// and `T: Send`.
+unsafe impl<T: Pod, L: WriteLock> Send for MmapGrowLock<T, L> {}
+// SAFETY: the elements are only written under the write lock, in the
+// slots past the published length, as in a `GrowLock`.
+unsafe impl<T: Pod, L: WriteLock> Sync for MmapGrowLock<T, L> {}
+
+/// Constructors, with the default write lock
+impl<T: Pod> MmapGrowLock<T> {
+    /// Creates a new file at `path`, holding an empty [`MmapGrowLock`]
+    /// of `capacity` elements.
+    ///
+    /// The file is initialized under a temporary name in the same
+    /// directory, and only linked at `path` once its header is synced, so
+    /// a failure or a crash never leaves a file without a header there.
+    ///
+    /// # Errors
+    /// Returns an error if the file already exists, if it can't be
+    /// created or mapped, or if its size overflows `isize::MAX`.
+    ///
+    /// # Safety
+    /// While the returned [`MmapGrowLock`] is alive, the file must not be
+    /// modified or truncated other than through it, see the
+    /// [module docs](self#safety).
+    pub unsafe fn create(
+        path: impl AsRef<Path>,
+        capacity: usize,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        let size = file_size::<Header, T>(capacity)?;
+        let temp = temp_path(path);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+        // linking fails if `path` exists, unlike renaming.
+        let lock = Self::init(file, size, capacity)
+            .and_then(|lock| fs::hard_link(&temp, path).map(|()| lock));
+        let _ = fs::remove_file(&temp);
+        lock
+    }
+    /// Initializes the new `file` with an empty array of `capacity`
+    /// elements, of `size` bytes.
+    fn init(file: File, size: usize, capacity: usize) -> io::Result<Self> {
+        lock_file(&file)?;
+        file.set_len(size as u64)?;
+        let lock = Self::map(file, size, capacity, 0)?;
+        let header = Header {
+            magic: MAGIC,
+            version: VERSION,
+            // `Header::read` checks them against `T`.
+            #[allow(clippy::cast_possible_truncation)]
+            elem_size: size_of::<T>() as u32,
+            #[allow(clippy::cast_possible_truncation)]
+            elem_align: align_of::<T>() as u32,
+            _reserved: 0,
+            capacity: capacity as u64,
+            len: AtomicU64::new(0),
+        };
+        // SAFETY: the mapping starts with room for the header, and it is
+        // page-aligned.
+        unsafe { lock.map.cast::<Header>().write(header) };
+        lock.msync(0..size_of::<Header>())?;
+        Ok(lock)
+    }
+    /// Opens the [`MmapGrowLock`] stored in the file at `path`, restoring
+    /// its flushed elements (see the [recovery rules](self#recovery)).
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be opened or mapped, if it is
+    /// already opened by another [`MmapGrowLock`], or if it doesn't hold
+    /// a [`MmapGrowLock<T>`].
+    ///
+    /// # Safety
+    /// While the returned [`MmapGrowLock`] is alive, the file must not be
+    /// modified or truncated other than through it, see the
+    /// [module docs](self#safety).
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        lock_file(&file)?;
+        let (capacity, len) = Header::read::<T>(&file)?;
+        let size = file_size::<Header, T>(capacity)?;
+        let file_len = file.metadata()?.len();
+        // the elements which are (entirely) in the file.
+        let in_file = usize::try_from(file_len)
+            .unwrap_or(usize::MAX)
+            .saturating_sub(data_offset::<Header, T>())
+            .checked_div(size_of::<T>())
+            .unwrap_or(usize::MAX);
+        if file_len < size as u64 {
+            file.set_len(size as u64)?;
+        }
+        let lock = Self::map(
+            file,
+            size,
+            capacity,
+            len.min(capacity).min(in_file),
+        )?;
+        if lock.len() != len {
+            // the discarded slots must not be restored by a later flush
+            // which skips them.
+            lock.store_durable_len(lock.len())?;
+        }
+        Ok(lock)
+    }
+    /// Opens the [`MmapGrowLock`] stored in the file at `path`, or creates
+    /// it with `capacity` if the file doesn't exist.
+    ///
+    /// The capacity of an existing file is kept.
+    ///
+    /// # Errors
+    /// See [`MmapGrowLock::open`] and [`MmapGrowLock::create`].
+    ///
+    /// # Safety
+    /// While the returned [`MmapGrowLock`] is alive, the file must not be
+    /// modified or truncated other than through it, see the
+    /// [module docs](self#safety).
+    pub unsafe fn open_or_create(
+        path: impl AsRef<Path>,
+        capacity: usize,
+    ) -> io::Result<Self> {
+        let path = path.as_ref();
+        // SAFETY: the caller upholds the contract of both.
+        match unsafe { Self::open(path) } {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                // SAFETY: as above.
+                unsafe { Self::create(path, capacity) }
+            }
+            res => res,
+        }
+    }
+    /// Maps the whole `file`, of `size` bytes.
+    fn map(
+        file: File,
+        size: usize,
+        capacity: usize,
+        len: usize,
+    ) -> io::Result<Self> {
+        let lock = Self {
+            map: map_file(&file, size, true)?,
+            map_len: size,
+            capacity,
+            len: AtomicUsize::new(len),
+            write_lock: LockState::new(),
+            file,
+            _marker: PhantomData,
+        };
+        Ok(lock)
+    }
+}
+
+impl<T: Pod, L: WriteLock> MmapGrowLock<T, L> {
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire)
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity
+    }
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// Returns the number of elements flushed to the file, which are
+    /// restored when the file is opened again.
+    #[inline]
+    #[must_use]
+    pub fn durable_len(&self) -> usize {
+        // only the writers store it, after the published length.
+        usize::try_from(self.header().len.load(Ordering::Relaxed))
+            .unwrap_or(usize::MAX)
+            .min(self.len())
+    }
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialized (any bit
+        // pattern is a valid `T`), and they are never mutated.
+        unsafe { slice::from_raw_parts(self.data(), self.len()) }
+    }
+    /// Locks the [`MmapGrowLock`] for writes, blocking the current thread
+    /// until it can be acquired, as [`GrowLock::write`].
+    ///
+    /// # Errors
+    /// Returns an error if the lock is poisoned, see
+    /// [`GrowLock::is_poisoned`].
+    ///
+    /// [`GrowLock::write`]: crate::GrowLock::write
+    /// [`GrowLock::is_poisoned`]: crate::GrowLock::is_poisoned
+    pub fn write(&self) -> LockResult<MmapGuard<'_, T, L>> {
+        let permit = self.write_lock.lock();
+        self.check_poison(MmapGuard { lock: self, permit })
+    }
+    /// Attempts to lock the [`MmapGrowLock`] for writes, without
+    /// blocking.
+    ///
+    /// # Errors
+    /// Returns an error if the lock is held by another writer, or if it
+    /// is poisoned.
+    pub fn try_write(&self) -> TryLockResult<MmapGuard<'_, T, L>> {
+        let permit =
+            self.write_lock.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self.check_poison(MmapGuard { lock: self, permit })?)
+    }
+    fn check_poison<G>(&self, guard: G) -> LockResult<G> {
+        if self.write_lock.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+    /// Returns `true` if a writer panicked while holding the write lock.
+    #[inline]
+    #[must_use]
+    pub fn is_poisoned(&self) -> bool {
+        self.write_lock.is_poisoned()
+    }
+    /// Clears the poisoning of the write lock.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.write_lock.clear_poison();
+    }
+    /// Moves the [`MmapGrowLock`] under another write lock backend, see
+    /// the [`lock`](crate::lock) module.
+    #[must_use]
+    pub fn with_lock<L2: WriteLock>(self) -> MmapGrowLock<T, L2> {
+        let this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used again, so its fields are moved.
+        let (write_lock, file) = unsafe {
+            (
+                ptr::read(&raw const this.write_lock),
+                ptr::read(&raw const this.file),
+            )
+        };
+        MmapGrowLock {
+            map: this.map,
+            map_len: this.map_len,
+            capacity: this.capacity,
+            len: AtomicUsize::new(this.len()),
+            write_lock: write_lock.with_raw(),
+            file,
+            _marker: PhantomData,
+        }
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping starts with the header, and it is
+        // page-aligned.
+        unsafe { self.map.cast().as_ref() }
+    }
+    fn data(&self) -> *mut T {
+        // SAFETY: the buffer is inside the mapping.
+        unsafe { self.map.as_ptr().add(data_offset::<Header, T>()).cast() }
+    }
+    /// Stores `len` as the durable length in the header, and syncs it.
+    fn store_durable_len(&self, len: usize) -> io::Result<()> {
+        self.header().len.store(len as u64, Ordering::Relaxed);
+        self.msync(
+            Header::LEN_OFFSET..Header::LEN_OFFSET + size_of::<u64>(),
+        )
+    }
+    /// Syncs the bytes of the mapping in `range` to the file.
+    fn msync(&self, range: Range<usize>) -> io::Result<()> {
+        if range.is_empty() {
+            return Ok(());
+        }
+        // `msync` takes a page-aligned address.
+        let start = range.start - range.start % page_size();
+        // SAFETY: the range is inside the mapping.
+        let res = unsafe {
+            libc::msync(
+                self.map.as_ptr().add(start).cast(),
+                range.end - start,
+                libc::MS_SYNC,
+            )
+        };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+impl<T: Pod, L: WriteLock> Drop for MmapGrowLock<T, L> {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is not used anymore. The elements are
+        // `Copy`, so nothing needs to be dropped.
+        unsafe { libc::munmap(self.map.as_ptr().cast(), self.map_len) };
+    }
+}
+
+impl<T: Pod, L: WriteLock> ops::Deref for MmapGrowLock<T, L> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+impl<T: Pod + fmt::Debug, L: WriteLock> fmt::Debug for MmapGrowLock<T, L> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// RAII structure giving the write access of a [`MmapGrowLock`], created
+/// by [`MmapGrowLock::write`].
+///
+/// The pushed elements are published to the readers at once, and they are
+/// flushed to the file by [`MmapGuard::flush`], or when the guard is
+/// dropped (ignoring the errors).
+#[must_use = "if unused the MmapGrowLock will immediately unlock"]
+pub struct MmapGuard<'lock, T: Pod, L: WriteLock = DefaultLock> {
+    lock: &'lock MmapGrowLock<T, L>,
+    permit: WritePermit,
+}
+
+impl<T: Pod, L: WriteLock> MmapGuard<'_, T, L> {
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self.lock.as_slice()
+    }
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        // only this guard stores it.
+        self.lock.len.load(Ordering::Relaxed)
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.lock.capacity
+    }
+    /// # Panics
+    /// Panics if `self.is_full()`.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.extend_from_slice(&[value]);
+    }
+    /// # Errors
+    /// Returns an error if `self.is_full()`.
+    #[inline]
+    pub fn try_push(&mut self, value: T) -> Result<(), LengthError> {
+        self.try_extend_from_slice(&[value])
+    }
+    /// # Panics
+    /// Panics if the elements don't fit in the remaining capacity.
+    #[inline]
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        assert!(
+            self.try_extend_from_slice(values).is_ok(),
+            "length overflow"
+        );
+    }
+    /// Appends all the elements of `values`, or none of them.
+    ///
+    /// # Errors
+    /// Returns an error if the elements don't fit in the remaining
+    /// capacity.
+    pub fn try_extend_from_slice(
+        &mut self,
+        values: &[T],
+    ) -> Result<(), LengthError> {
+        // SAFETY: the buffer holds `capacity` elements, and we hold the
+        // write lock.
+        let len = unsafe {
+            append(self.lock.data(), self.len(), self.capacity(), values)
+        }?;
+        self.lock.len.store(len, Ordering::Release);
+        Ok(())
+    }
+    /// Syncs the pushed elements to the file, and then the durable length
+    /// in its header, so they are restored when the file is opened again.
+    ///
+    /// # Errors
+    /// Returns an error if `msync` fails. The elements stay published,
+    /// and the next flush retries.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let len = self.len();
+        let durable = self.lock.durable_len();
+        if durable == len {
+            return Ok(());
+        }
+        let offset = data_offset::<Header, T>();
+        let size = size_of::<T>();
+        self.lock
+            .msync(offset + durable * size..offset + len * size)?;
+        // a crash before this point loses the new elements, but never
+        // restores torn ones.
+        self.lock.store_durable_len(len)
+    }
+}
+
+impl<T: Pod, L: WriteLock> ops::Deref for MmapGuard<'_, T, L> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T: Pod, L: WriteLock> Drop for MmapGuard<'_, T, L> {
+    fn drop(&mut self) {
+        // call `flush` to handle the errors.
+        let _ = self.flush();
+        // SAFETY: the permit comes from the lock, and it is never used
+        // again.
+        unsafe { self.lock.write_lock.unlock(&self.permit) };
+    }
+}
+
+/// Copies `values` after the first `len` elements of the buffer `data`,
+/// holding `capacity` elements, and returns the new length.
+///
+/// # Errors
+/// Returns an error if the elements don't fit in the remaining capacity,
+/// without copying any.
+///
+/// # Safety
+/// `data` must point to a buffer of `capacity` elements, whose slots past
+/// `len` are not published (i.e. the caller holds the write lock).
+pub(crate) unsafe fn append<T: Pod>(
+    data: *mut T,
+    len: usize,
+    capacity: usize,
+    values: &[T],
+) -> Result<usize, LengthError> {
+    if values.len() > capacity - len {
+        return Err(LengthError);
+    }
+    // SAFETY: the slots in `len..len + values.len()` are in the buffer,
+    // and they are not published, so no one reads them.
+    unsafe {
+        ptr::copy_nonoverlapping(
+            values.as_ptr(),
+            data.add(len),
+            values.len(),
+        );
+    }
+    Ok(len + values.len())
+}
+
+/// Maps the first `size` bytes of `file` in shared memory, read-only or
+/// not.
+pub(crate) fn map_file(
+    file: &File,
+    size: usize,
+    writable: bool,
+) -> io::Result<NonNull<u8>> {
+    let prot = if writable {
+        libc::PROT_READ | libc::PROT_WRITE
+    } else {
+        libc::PROT_READ
+    };
+    // SAFETY: this creates a new mapping, which doesn't alias any memory.
+    let ptr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size,
+            prot,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        Err(io::Error::last_os_error())
+    } else {
+        // SAFETY: `mmap` succeeded, so `ptr` is not null.
+        Ok(unsafe { NonNull::new_unchecked(ptr.cast()) })
+    }
+}
+
+/// Returns a temporary path next to `path`, unique in this process.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut name = path.file_name().unwrap_or_default().to_owned();
+    name.push(format!(
+        ".{}-{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Locks `file` exclusively, without blocking, so a file is opened by a
+/// single [`MmapGrowLock`].
+fn lock_file(file: &File) -> io::Result<()> {
+    // SAFETY: `file` is an open file descriptor.
+    if unsafe {
+        libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB)
+    } == 0
+    {
+        return Ok(());
+    }
+    let e = io::Error::last_os_error();
+    if e.kind() == io::ErrorKind::WouldBlock {
+        Err(io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "the file is already opened by another `MmapGrowLock`",
+        ))
+    } else {
+        Err(e)
+    }
+}
+
+fn page_size() -> usize {
+    // SAFETY: `sysconf` has no preconditions.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(size).unwrap_or(4096)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use {
+        super::{Header, MmapGrowLock, Pod},
+        std::{
+            fs,
+            io::{self, ErrorKind},
+            mem,
+            path::Path,
+            process,
+            sync::atomic::{AtomicUsize, Ordering},
+            thread,
+        },
+    };
+
+    /// A new name of a temporary file or segment, removed on drop.
+    pub(crate) struct TempName(pub(crate) String, fn(&str));
+    impl TempName {
+        /// Returns a name starting with `prefix`, unique in the system,
+        /// which is removed with `remove`.
+        pub(crate) fn new(prefix: &str, remove: fn(&str)) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let name = format!(
+                "{prefix}-{}-{}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed),
+            );
+            remove(&name);
+            Self(name, remove)
+        }
+    }
+    impl Drop for TempName {
+        fn drop(&mut self) {
+            (self.1)(&self.0);
+        }
+    }
+
+    pub(crate) const THREADS: usize = 8;
+    pub(crate) const PUSHES: usize = 100;
+
+    /// Pushes `0..THREADS * PUSHES` from `THREADS` threads, each with its
+    /// own `writer()`, while checking that `elements` only returns pushed
+    /// values. Returns the elements once all of them are pushed.
+    pub(crate) fn push_concurrently<W: Send>(
+        writer: impl Fn() -> W,
+        push: impl Fn(&W, usize) + Sync,
+        elements: impl Fn() -> Vec<usize>,
+    ) -> Vec<usize> {
+        thread::scope(|s| {
+            let handles: Vec<_> = (0..THREADS)
+                .map(|t| {
+                    let (writer, push) = (writer(), &push);
+                    s.spawn(move || {
+                        for i in 0..PUSHES {
+                            push(&writer, t * PUSHES + i);
+                        }
+                    })
+                })
+                .collect();
+            while !handles
+                .iter()
+                .all(thread::ScopedJoinHandle::is_finished)
+            {
+                // readers see initialized elements.
+                assert!(elements().iter().all(|&n| n < THREADS * PUSHES));
+            }
+        });
+        let elements = elements();
+        let mut sorted = elements.clone();
+        sorted.sort_unstable();
+        assert!(sorted.into_iter().eq(0..THREADS * PUSHES));
+        elements
+    }
+
+    fn create<T: Pod>(
+        path: &TempName,
+        capacity: usize,
+    ) -> io::Result<MmapGrowLock<T>> {
+        // SAFETY: the tests only modify a file while no `MmapGrowLock` is
+        // open on it.
+        unsafe { MmapGrowLock::create(&path.0, capacity) }
+    }
+    fn open<T: Pod>(path: &TempName) -> io::Result<MmapGrowLock<T>> {
+        // SAFETY: see `create`.
+        unsafe { MmapGrowLock::open(&path.0) }
+    }
+    fn open_or_create<T: Pod>(
+        path: &TempName,
+        capacity: usize,
+    ) -> io::Result<MmapGrowLock<T>> {
+        // SAFETY: see `create`.
+        unsafe { MmapGrowLock::open_or_create(&path.0, capacity) }
+    }
+
+    fn temp_path() -> TempName {
+        let prefix = std::env::temp_dir().join("growlock-mmap");
+        TempName::new(&prefix.to_string_lossy(), |path| {
+            let _ = fs::remove_file(path);
+        })
+    }
+
+    #[test]
+    fn reopen() {
+        let path = temp_path();
+        let lock = create::<u32>(&path, 10).unwrap();
+        assert!(lock.is_empty());
+        {
+            let mut guard = lock.write().unwrap();
+            guard.push(1);
+            guard.extend_from_slice(&[2, 3]);
+            assert_eq!(&lock[..], &[1, 2, 3]);
+            assert_eq!(lock.durable_len(), 0);
+            guard.flush().unwrap();
+            assert_eq!(lock.durable_len(), 3);
+            guard.push(4);
+        }
+        assert_eq!(lock.durable_len(), 4);
+        drop(lock);
+
+        let lock = open::<u32>(&path).unwrap();
+        assert_eq!((&lock[..], lock.capacity()), (&[1, 2, 3, 4][..], 10));
+        lock.write().unwrap().push(5);
+        drop(lock);
+        let lock = open_or_create::<u32>(&path, 3).unwrap();
+        assert_eq!((lock.len(), lock.capacity()), (5, 10));
+    }
+
+    #[test]
+    fn full() {
+        let path = temp_path();
+        let lock = create::<[u8; 3]>(&path, 2).unwrap();
+        let mut guard = lock.write().unwrap();
+        assert!(guard.try_extend_from_slice(&[[1; 3]; 3]).is_err());
+        guard.extend_from_slice(&[[1; 3], [2; 3]]);
+        assert!(guard.is_full());
+        assert!(guard.try_push([3; 3]).is_err());
+        assert_eq!(&guard[..], &[[1; 3], [2; 3]]);
+    }
+
+    #[test]
+    fn torn_tail() {
+        let path = temp_path();
+        let lock = create::<u64>(&path, 8).unwrap();
+        lock.write().unwrap().extend_from_slice(&[1, 2]);
+        let mut guard = lock.write().unwrap();
+        guard.extend_from_slice(&[3, 4]);
+        // a crash: the new elements are never flushed.
+        mem::forget(guard);
+        drop(lock);
+
+        let lock = open::<u64>(&path).unwrap();
+        assert_eq!(&lock[..], &[1, 2]);
+        lock.write().unwrap().push(5);
+        assert_eq!(&lock[..], &[1, 2, 5]);
+    }
+
+    #[test]
+    fn corrupted() {
+        let path = temp_path();
+        let lock = create::<u16>(&path, 4).unwrap();
+        lock.write().unwrap().extend_from_slice(&[1, 2, 3]);
+        drop(lock);
+
+        // the durable length is clamped to the capacity.
+        let mut bytes = fs::read(&path.0).unwrap();
+        bytes[Header::LEN_OFFSET..][..8]
+            .copy_from_slice(&100u64.to_ne_bytes());
+        fs::write(&path.0, &bytes).unwrap();
+        assert_eq!(open::<u16>(&path).unwrap().len(), 4);
+
+        // a truncated file is extended, keeping the elements it holds.
+        bytes[Header::LEN_OFFSET..][..8]
+            .copy_from_slice(&3u64.to_ne_bytes());
+        fs::write(&path.0, &bytes[..bytes.len() - 3]).unwrap();
+        let lock = open::<u16>(&path).unwrap();
+        assert_eq!((&lock[..], lock.capacity()), (&[1, 2][..], 4));
+        assert_eq!(lock.durable_len(), 2);
+        // the clamped length is stored, so an unflushed element in the
+        // discarded slot is not restored.
+        let mut guard = lock.write().unwrap();
+        guard.push(99);
+        assert_eq!(lock.durable_len(), 2);
+        mem::forget(guard);
+        drop(lock);
+        let lock = open::<u16>(&path).unwrap();
+        assert_eq!(&lock[..], &[1, 2]);
+        drop(lock);
+
+        let err = open::<u32>(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        fs::write(&path.0, b"not a growlock").unwrap();
+        let err = open::<u16>(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn exclusive() {
+        let path = temp_path();
+        let lock = create::<u8>(&path, 4).unwrap();
+        let err = create::<u8>(&path, 4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        // the temporary file of the failed creation is removed.
+        let file = Path::new(&path.0);
+        let temp = format!("{}.", file.file_name().unwrap().display());
+        let dir = fs::read_dir(file.parent().unwrap()).unwrap();
+        assert!(
+            !dir.map(|entry| entry.unwrap().file_name())
+                .any(|name| name.to_string_lossy().starts_with(&temp))
+        );
+        let err = open::<u8>(&path).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WouldBlock);
+        drop(lock);
+        open::<u8>(&path).unwrap();
+    }
+
+    #[test]
+    fn concurrent() {
+        let path = temp_path();
+        let lock = create::<usize>(&path, THREADS * PUSHES).unwrap();
+        let elements = push_concurrently(
+            || &lock,
+            |lock, n| lock.write().unwrap().push(n),
+            || lock.to_vec(),
+        );
+        assert!(lock.is_full());
+        drop(lock);
+
+        let lock = open::<usize>(&path).unwrap();
+        assert_eq!(lock[..], elements);
+    }
+}
@@ -0,0 +1,153 @@
+//! FIFO queue of the asynchronous writers of a
+//! [`GrowLock`](crate::GrowLock).
+//!
+//! An asynchronous writer which can't take the write lock enqueues its
+//! [`Waker`], and only the writer at the front of the queue may try to
+//! take the write lock again. When a guard is dropped, it wakes the front
+//! of the queue up, so the lock is handed over to the asynchronous writers
+//! in the order they arrived.
+//!
+//! # Protocol
+//! * a writer enqueues itself (updating `len`), and then tries to take the
+//!   write lock.
+//! * a guard releases the write lock, and then checks `len`: only if
+//!   someone is waiting it locks the queue and wakes the front up.
+//!
+//! As for [`Waiters`](crate::wait::Waiters), both sides issue a `SeqCst`
+//! fence between their write and their read.
+
+#[cfg(not(loom))]
+use std::sync::{
+    Mutex, MutexGuard,
+    atomic::{self, AtomicUsize, Ordering},
+};
+use std::{
+    collections::VecDeque,
+    sync::PoisonError,
+    task::{Context, Poll, Waker},
+};
+
+#[cfg(loom)]
+use loom::sync::{
+    Mutex, MutexGuard,
+    atomic::{self, AtomicUsize, Ordering},
+};
+
+/// Asynchronous writers waiting for the write lock of a
+/// [`GrowLock`](crate::GrowLock).
+pub(crate) struct WriteQueue {
+    /// Length of the queue.
+    len: AtomicUsize,
+    queue: Mutex<Queue>,
+}
+
+struct Queue {
+    /// Ticket of the next writer to enqueue.
+    next: u64,
+    /// Tickets and wakers of the writers, in the order they arrived.
+    writers: VecDeque<(u64, Waker)>,
+}
+
+impl WriteQueue {
+    pub(crate) fn new() -> Self {
+        Self {
+            len: AtomicUsize::new(0),
+            queue: Mutex::new(Queue {
+                next: 0,
+                writers: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Tries to take the write lock with `try_lock` when it's the turn of
+    /// the writer holding `ticket`, enqueueing it otherwise.
+    ///
+    /// `ticket` must be `None` for the first poll of a writer, and is
+    /// `None` again once the writer has left the queue.
+    pub(crate) fn poll_acquire<R>(
+        &self,
+        ticket: &mut Option<u64>,
+        cx: &Context<'_>,
+        mut try_lock: impl FnMut() -> Option<R>,
+    ) -> Poll<R> {
+        if ticket.is_none()
+            && self.len.load(Ordering::Relaxed) == 0
+            && let Some(locked) = try_lock()
+        {
+            return Poll::Ready(locked);
+        }
+
+        {
+            let mut queue = self.lock();
+            let ticket = *ticket.get_or_insert_with(|| {
+                let new = queue.next;
+                queue.next += 1;
+                queue.writers.push_back((new, cx.waker().clone()));
+                self.len.fetch_add(1, Ordering::Relaxed);
+                new
+            });
+            // the waker of the task may change between two polls.
+            if let Some((_, waker)) =
+                queue.writers.iter_mut().find(|(t, _)| *t == ticket)
+            {
+                waker.clone_from(cx.waker());
+            }
+            if queue.writers.front().map(|&(t, _)| t) != Some(ticket) {
+                return Poll::Pending;
+            }
+        }
+        atomic::fence(Ordering::SeqCst);
+        match try_lock() {
+            Some(locked) => {
+                // we are the front of the queue, and we hold the write
+                // lock: the next writer is woken up when
+                // our guard is dropped.
+                self.lock().writers.pop_front();
+                self.len.fetch_sub(1, Ordering::Relaxed);
+                *ticket = None;
+                Poll::Ready(locked)
+            }
+            None => Poll::Pending,
+        }
+    }
+
+    /// Removes the writer holding `ticket` from the queue, handing its
+    /// turn over to the next writer.
+    pub(crate) fn cancel(&self, ticket: u64) {
+        let mut queue = self.lock();
+        let Some(index) =
+            queue.writers.iter().position(|&(t, _)| t == ticket)
+        else {
+            return;
+        };
+        queue.writers.remove(index);
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        // the writer may have been woken up by a guard, and never polled.
+        if index == 0
+            && let Some((_, waker)) = queue.writers.front()
+        {
+            waker.wake_by_ref();
+        }
+    }
+
+    /// Wakes up the front of the queue, if any. Must be called after
+    /// releasing the write lock.
+    #[inline]
+    pub(crate) fn notify(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.len.load(Ordering::Relaxed) != 0 {
+            self.notify_slow();
+        }
+    }
+    #[cold]
+    fn notify_slow(&self) {
+        if let Some((_, waker)) = self.lock().writers.front() {
+            waker.wake_by_ref();
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        // the queue is always consistent, so poisoning is irrelevant.
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -2,14 +2,12 @@
 
 use {
     crate::{
+        allocator::{Allocator, Global},
         cap::{Cap, is_zst},
         error::TryReserveError,
     },
-    std::{
-        alloc::{Allocator, Global, Layout, handle_alloc_error},
-        marker::PhantomData,
-        ptr::NonNull,
-    },
+    alloc::alloc::handle_alloc_error,
+    core::{alloc::Layout, marker::PhantomData, ptr::NonNull},
 };
 
 /// Inner buffer of [`GrowLock`](crate::GrowLock).
@@ -69,6 +67,60 @@ impl<T, A: Allocator> RawGrowLock<T, A> {
             }
         }
     }
+    /// Reallocates the buffer with the new capacity `cap`, in the same
+    /// allocator.
+    ///
+    /// # Errors
+    /// If any of these conditions happen, an error is returned and the
+    /// buffer is left untouched:
+    /// * `cap * size_of::<T>` overflows `isize::MAX`
+    /// * memory is exhausted
+    ///
+    /// # Safety
+    /// When shrinking, the elements past `cap` must not be used anymore.
+    pub(crate) unsafe fn try_realloc(
+        &mut self,
+        cap: Cap,
+    ) -> Result<(), TryReserveError> {
+        if cap == self.cap {
+            return Ok(());
+        }
+        let Ok(new_layout) = Layout::array::<T>(cap.get()) else {
+            return Err(TryReserveError::CapacityOverflow);
+        };
+
+        let ptr = match self.memory_layout() {
+            None => self
+                .alloc
+                .allocate(new_layout)
+                .map_err(|_| TryReserveError::AllocError(new_layout))?
+                .cast(),
+            Some((ptr, layout)) if cap == Cap::ZERO => {
+                // SAFETY: we allocated this block of memory with this ptr
+                // and this layout
+                unsafe { self.alloc.deallocate(ptr, layout) };
+                NonNull::dangling()
+            }
+            Some((ptr, layout)) => {
+                // SAFETY: we allocated this block of memory with this ptr
+                // and this layout, and both layouts have the alignment of
+                // `T`.
+                let block = unsafe {
+                    if cap.get() > self.cap.get() {
+                        self.alloc.grow(ptr, layout, new_layout)
+                    } else {
+                        self.alloc.shrink(ptr, layout, new_layout)
+                    }
+                };
+                block
+                    .map_err(|_| TryReserveError::AllocError(new_layout))?
+                    .cast()
+            }
+        };
+        self.ptr = ptr;
+        self.cap = cap;
+        Ok(())
+    }
     /// Creates a new [`RawGrowLock<T>`] directly from a
     /// [`NonNull`] pointer, a capacity, and an allocator.
     ///
@@ -149,7 +201,7 @@ impl<T, A: Allocator> RawGrowLock<T, A> {
         }
     }
     #[inline]
-    #[cfg(all(test, not(loom)))]
+    #[cfg(all(test, feature = "std", not(loom)))]
     pub(crate) const fn raw_cap(&self) -> Cap {
         self.cap
     }
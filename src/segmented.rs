@@ -0,0 +1,961 @@
+//! A [`GrowLock`](crate::GrowLock) whose capacity can grow.
+//!
+//! [`SegmentedGrowLock`] stores its elements in a list of segments: each
+//! segment is twice as big as the previous one and, once allocated, it is
+//! never moved nor freed until the lock is dropped. This means that
+//! growing never invalidates a reference to an element, and readers can
+//! still access the elements without locking.
+//!
+//! The price to pay is that the elements are not contiguous in memory, so
+//! unlike [`GrowLock`](crate::GrowLock), a [`SegmentedGrowLock`] cannot
+//! be seen as a slice.
+
+#[cfg(not(loom))]
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+use {
+    crate::{
+        allocator::{Allocator, Global},
+        cap::is_zst,
+        error::TryReserveError,
+        lock::{DefaultLock, LockState, WriteLock, WritePermit},
+    },
+    std::{
+        alloc::{Layout, handle_alloc_error},
+        array, fmt,
+        iter::FusedIterator,
+        marker::PhantomData,
+        mem::ManuallyDrop,
+        ops,
+        ptr::{self, NonNull},
+        slice,
+        sync::{LockResult, PoisonError, TryLockError, TryLockResult},
+        time::{Duration, Instant},
+    },
+};
+
+/// Maximum number of segments. As each segment doubles the capacity, the
+/// capacity overflows before this is reached.
+const MAX_SEGMENTS: usize = usize::BITS as usize;
+/// Capacity of the first segment when no capacity (or a smaller one) is
+/// requested.
+const MIN_SEGMENT_CAP: usize = 4;
+
+/// Returns the index of the segment containing the element at `index`,
+/// and the offset of the element in that segment.
+///
+/// `1 << shift` is the capacity of the first segment.
+#[inline]
+const fn locate(shift: u32, index: usize) -> (usize, usize) {
+    // Segment `seg` holds the indices in
+    // `segment_start(seg)..segment_start(seg + 1)`, i.e.
+    // `2^seg <= index / 2^shift + 1 < 2^(seg + 1)`.
+    let seg = ((index >> shift) + 1).ilog2() as usize;
+    (seg, index - segment_start(shift, seg))
+}
+/// Returns the index of the first element of the segment `seg`.
+#[inline]
+const fn segment_start(shift: u32, seg: usize) -> usize {
+    ((1 << seg) - 1) << shift
+}
+
+/// An append-only array like [`GrowLock`](crate::GrowLock) which
+/// allocates a new segment instead of overflowing its capacity.
+///
+/// It can simultaneously have any number of readers **and** one writer.
+/// Elements are never moved, so the references obtained from a
+/// [`SegmentedGrowLock`] stay valid while it grows.
+///
+/// # Examples
+/// ```
+/// use growlock::segmented::SegmentedGrowLock;
+///
+/// let lock = SegmentedGrowLock::with_capacity(2);
+/// let mut guard = lock.write().unwrap();
+/// guard.extend([1, 2]);
+///
+/// let first = &lock[0];
+/// // this would panic with a `GrowLock`
+/// guard.push(3);
+///
+/// assert_eq!(*first, 1);
+/// assert!(lock.capacity() >= 3);
+/// assert_eq!(lock.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+/// ```
+///
+/// As [`GrowLock`](crate::GrowLock), it is generic over the backend of its
+/// write lock, see the [`lock`](crate::lock) module.
+pub struct SegmentedGrowLock<
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    /// Pointers to the segments. Only the first `allocated` are non-null.
+    ///
+    /// The writer stores a pointer before publishing any element of its
+    /// segment through `len`, so the readers can load them with
+    /// [`Ordering::Relaxed`] after loading `len`.
+    segments: [AtomicPtr<T>; MAX_SEGMENTS],
+    /// `log2` of the capacity of the first segment.
+    shift: u32,
+    /// Number of allocated segments.
+    allocated: AtomicUsize,
+    /// Number of published elements.
+    ///
+    /// This follows the same publication protocol of the `len` of
+    /// [`GrowLock`](crate::GrowLock).
+    len: AtomicUsize,
+    write_lock: LockState<L>,
+    alloc: A,
+    _marker: PhantomData<T>,
+}
+
+/// # Safety:
+/// If both `T` and `A` are [`Send`], it is safe to transfer an
+/// [`SegmentedGrowLock<T, A>`] between threads as we have exclusive
+/// ownership of the segments.
+unsafe impl<T, A, L> Send for SegmentedGrowLock<T, A, L>
+where
+    T: Send,
+    A: Send + Allocator,
+    L: WriteLock,
+{
+}
+/// # Safety:
+/// All writes to the segments are handled along the write lock, the rest
+/// of the shared state is atomic.
+unsafe impl<T, A, L> Sync for SegmentedGrowLock<T, A, L>
+where
+    T: Sync + Send,
+    A: Sync + Send + Allocator,
+    L: WriteLock,
+{
+}
+
+/// [`Global`] only methods
+impl<T> SegmentedGrowLock<T> {
+    /// Creates a new, empty [`SegmentedGrowLock<T>`] without allocating.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::segmented::SegmentedGrowLock;
+    ///
+    /// let lock: SegmentedGrowLock<u8> = SegmentedGrowLock::new();
+    /// assert_eq!(lock.capacity(), 0);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::new_in(Global)
+    }
+    /// Creates a new [`SegmentedGrowLock<T>`] whose first segment can hold
+    /// at least `capacity` elements, returning an error if the allocation
+    /// fails.
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// * `capacity * size_of::<T>` overflows `isize::MAX`
+    /// * memory is exhausted
+    #[inline]
+    pub fn try_with_capacity(
+        capacity: usize,
+    ) -> Result<Self, TryReserveError> {
+        Self::try_with_capacity_in(capacity, Global)
+    }
+    /// Creates a new [`SegmentedGrowLock<T>`] whose first segment can hold
+    /// at least `capacity` elements.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::segmented::SegmentedGrowLock;
+    ///
+    /// let lock: SegmentedGrowLock<u8> = SegmentedGrowLock::with_capacity(10);
+    /// assert!(lock.capacity() >= 10);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_in(capacity, Global)
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> SegmentedGrowLock<T, A, L> {
+    /// Creates a new, empty [`SegmentedGrowLock<T>`] in the provided
+    /// allocator, without allocating.
+    #[inline]
+    #[must_use]
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_shift(MIN_SEGMENT_CAP.ilog2(), alloc)
+    }
+    /// Creates a new [`SegmentedGrowLock<T>`] in the provided allocator,
+    /// whose first segment can hold at least `capacity` elements,
+    /// returning an error if the allocation fails.
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// * `capacity * size_of::<T>` overflows `isize::MAX`
+    /// * memory is exhausted
+    pub fn try_with_capacity_in(
+        capacity: usize,
+        alloc: A,
+    ) -> Result<Self, TryReserveError> {
+        let Some(first_cap) =
+            capacity.max(MIN_SEGMENT_CAP).checked_next_power_of_two()
+        else {
+            return Err(TryReserveError::CapacityOverflow);
+        };
+        let this = Self::with_shift(first_cap.ilog2(), alloc);
+        // ZSTs never need a segment.
+        if capacity != 0 && !is_zst::<T>() {
+            this.try_grow()?;
+        }
+        Ok(this)
+    }
+    /// Creates a new [`SegmentedGrowLock<T>`] in the provided allocator,
+    /// whose first segment can hold at least `capacity` elements.
+    #[inline]
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)]
+    pub fn with_capacity_in(capacity: usize, alloc: A) -> Self {
+        match Self::try_with_capacity_in(capacity, alloc) {
+            Ok(this) => this,
+            Err(e @ TryReserveError::CapacityOverflow) => panic!("{e}"),
+            Err(TryReserveError::AllocError(layout)) => {
+                handle_alloc_error(layout)
+            }
+        }
+    }
+    fn with_shift(shift: u32, alloc: A) -> Self {
+        Self {
+            segments: array::from_fn(|_| AtomicPtr::new(ptr::null_mut())),
+            shift,
+            allocated: AtomicUsize::new(0),
+            len: AtomicUsize::new(0),
+            write_lock: LockState::new(),
+            alloc,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements in the [`SegmentedGrowLock`].
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        // Acquire: pairs with the Release store of the writer, so all the
+        // elements (and segments) below `len` are visible to this thread.
+        self.len.load(Ordering::Acquire)
+    }
+    /// Returns `true` if the [`SegmentedGrowLock`] contains no elements.
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the number of elements the allocated segments can hold.
+    ///
+    /// Unlike [`GrowLock::capacity`](crate::GrowLock::capacity), this is
+    /// not a limit: pushing past it allocates a new segment.
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        if is_zst::<T>() {
+            usize::MAX
+        } else {
+            segment_start(
+                self.shift,
+                self.allocated.load(Ordering::Relaxed),
+            )
+        }
+    }
+    /// Returns a reference to the underlying allocator.
+    #[inline]
+    #[must_use]
+    pub const fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Returns a reference to the element at `index`, or `None` if it is
+    /// out of bounds.
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        if index >= self.len() {
+            return None;
+        }
+        // SAFETY: `index < len`, so the element is initialized and
+        // visible to this thread (see `len`).
+        Some(unsafe { self.slot(index).as_ref() })
+    }
+    /// Returns an iterator over the published elements.
+    ///
+    /// The iterator only yields the elements published when it was
+    /// created.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T, A, L> {
+        Iter {
+            segments: self.segments(),
+            current: [].iter(),
+        }
+    }
+    /// Returns an iterator over the published part of each segment, as
+    /// slices.
+    ///
+    /// The iterator only yields the elements published when it was
+    /// created.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::segmented::SegmentedGrowLock;
+    ///
+    /// let lock = SegmentedGrowLock::with_capacity(4);
+    /// lock.write().unwrap().extend(0..6);
+    ///
+    /// let mut segments = lock.segments();
+    /// assert_eq!(segments.next(), Some(&[0, 1, 2, 3][..]));
+    /// assert_eq!(segments.next(), Some(&[4, 5][..]));
+    /// assert_eq!(segments.next(), None);
+    /// ```
+    #[inline]
+    pub fn segments(&self) -> Segments<'_, T, A, L> {
+        Segments {
+            lock: self,
+            seg: 0,
+            start: 0,
+            len: self.len(),
+        }
+    }
+
+    /// Locks this [`SegmentedGrowLock`] for writes, blocking the current
+    /// thread until it can be acquired. Note that this does not lock the
+    /// [`SegmentedGrowLock`] for reads.
+    ///
+    /// # Errors
+    /// This function will return an error if the lock is poisoned, see
+    /// [`GrowLock::write`](crate::GrowLock::write).
+    #[inline]
+    #[doc(alias = "lock")]
+    pub fn write(&self) -> LockResult<SegmentedGrowGuard<'_, T, A, L>> {
+        let permit = self.write_lock.lock();
+        self.check_poison(SegmentedGrowGuard::new(self, permit))
+    }
+    /// Attempts to lock this [`SegmentedGrowLock`] with exclusive write
+    /// access, without blocking.
+    ///
+    /// # Errors
+    /// This function will return an error if the lock is poisoned or
+    /// already locked, see
+    /// [`GrowLock::try_write`](crate::GrowLock::try_write).
+    #[inline]
+    #[doc(alias = "try_lock")]
+    pub fn try_write(
+        &self,
+    ) -> TryLockResult<SegmentedGrowGuard<'_, T, A, L>> {
+        let permit =
+            self.write_lock.try_lock().ok_or(TryLockError::WouldBlock)?;
+        Ok(self.check_poison(SegmentedGrowGuard::new(self, permit))?)
+    }
+    /// Attempts to lock this [`SegmentedGrowLock`] with exclusive write
+    /// access, blocking the current thread for at most `timeout`.
+    ///
+    /// # Errors
+    /// This function will return an error if the lock is poisoned, or if
+    /// it could not be acquired before the timeout expired, see
+    /// [`GrowLock::try_write_for`](crate::GrowLock::try_write_for).
+    #[inline]
+    pub fn try_write_for(
+        &self,
+        timeout: Duration,
+    ) -> TryLockResult<SegmentedGrowGuard<'_, T, A, L>> {
+        // if the deadline overflows, we wait forever.
+        self.try_write_deadline(Instant::now().checked_add(timeout))
+    }
+    /// Attempts to lock this [`SegmentedGrowLock`] with exclusive write
+    /// access, blocking the current thread until `deadline` at most.
+    ///
+    /// # Errors
+    /// This function will return an error if the lock is poisoned, or if
+    /// it could not be acquired before `deadline`, see
+    /// [`GrowLock::try_write_until`](crate::GrowLock::try_write_until).
+    #[inline]
+    pub fn try_write_until(
+        &self,
+        deadline: Instant,
+    ) -> TryLockResult<SegmentedGrowGuard<'_, T, A, L>> {
+        self.try_write_deadline(Some(deadline))
+    }
+    fn try_write_deadline(
+        &self,
+        deadline: Option<Instant>,
+    ) -> TryLockResult<SegmentedGrowGuard<'_, T, A, L>> {
+        let permit = match deadline {
+            None => self.write_lock.lock(),
+            Some(deadline) => self
+                .write_lock
+                .try_lock_until(deadline)
+                .ok_or(TryLockError::WouldBlock)?,
+        };
+        Ok(self.check_poison(SegmentedGrowGuard::new(self, permit))?)
+    }
+    /// Returns `true` if the [`SegmentedGrowLock`] is poisoned, i.e. a
+    /// writer panicked while holding the write lock, see
+    /// [`GrowLock::is_poisoned`](crate::GrowLock::is_poisoned).
+    #[inline]
+    #[must_use]
+    pub fn is_poisoned(&self) -> bool {
+        self.write_lock.is_poisoned()
+    }
+    /// Clears the poisoned state of the [`SegmentedGrowLock`].
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.write_lock.clear_poison();
+    }
+    /// Disables the poisoning of the [`SegmentedGrowLock`], clearing it,
+    /// see [`GrowLock::without_poisoning`](crate::GrowLock::without_poisoning).
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::segmented::SegmentedGrowLock;
+    /// use std::thread;
+    ///
+    /// let lock = SegmentedGrowLock::new().without_poisoning();
+    /// let _ = thread::scope(|s| {
+    ///     s.spawn(|| {
+    ///         let _guard = lock.write().unwrap();
+    ///         panic!();
+    ///     })
+    ///     .join()
+    /// });
+    /// assert!(!lock.is_poisoned());
+    /// lock.write().unwrap().push(1);
+    /// ```
+    #[inline]
+    #[must_use]
+    pub fn without_poisoning(mut self) -> Self {
+        self.write_lock.disable_poisoning();
+        self
+    }
+    /// Wraps the guard of a writer in a [`PoisonError`] if the
+    /// [`SegmentedGrowLock`] is poisoned.
+    #[inline]
+    fn check_poison<G>(&self, guard: G) -> LockResult<G> {
+        if self.write_lock.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+    /// Moves the elements of the [`SegmentedGrowLock`] under another write
+    /// lock backend, see the [`lock`](crate::lock) module.
+    ///
+    /// This keeps the same segments, so no element is moved, and the
+    /// poisoning of the lock.
+    ///
+    /// # Examples
+    /// ```
+    /// use growlock::{lock::StdLock, segmented::SegmentedGrowLock};
+    ///
+    /// let lock = SegmentedGrowLock::new();
+    /// lock.write().unwrap().push(1);
+    /// let lock = lock.with_lock::<StdLock>();
+    /// assert_eq!(lock[0], 1);
+    /// ```
+    #[must_use]
+    pub fn with_lock<L2: WriteLock>(self) -> SegmentedGrowLock<T, A, L2> {
+        let mut this = ManuallyDrop::new(self);
+        let segments = array::from_fn(|seg| {
+            AtomicPtr::new(this.segments[seg].load(Ordering::Relaxed))
+        });
+        let write_lock = this.write_lock.with_raw();
+        // SAFETY: `this` is never used nor dropped again, so the allocator
+        // is only moved out once, and the old write lock is dropped once.
+        let alloc = unsafe {
+            ptr::drop_in_place(&raw mut this.write_lock);
+            ptr::read(&raw const this.alloc)
+        };
+        SegmentedGrowLock {
+            segments,
+            shift: this.shift,
+            allocated: AtomicUsize::new(
+                this.allocated.load(Ordering::Relaxed),
+            ),
+            len: AtomicUsize::new(this.len.load(Ordering::Relaxed)),
+            write_lock,
+            alloc,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns a pointer to the slot at `index`.
+    ///
+    /// # Safety
+    /// `index` must be less than `self.capacity()`, and the segment
+    /// containing it must be visible to this thread.
+    #[inline]
+    unsafe fn slot(&self, index: usize) -> NonNull<T> {
+        if is_zst::<T>() {
+            return NonNull::dangling();
+        }
+        let (seg, offset) = locate(self.shift, index);
+        let ptr = self.segments[seg].load(Ordering::Relaxed);
+        // SAFETY: the segment is allocated, and `offset` is less than its
+        // capacity.
+        unsafe { NonNull::new_unchecked(ptr).add(offset) }
+    }
+    /// Returns the capacity of the segment `seg`, if it doesn't overflow.
+    #[inline]
+    fn segment_cap(&self, seg: usize) -> Option<usize> {
+        1usize.checked_shl(self.shift + u32::try_from(seg).ok()?)
+    }
+    /// Allocates a new segment.
+    ///
+    /// Only the writer (or the constructor) may call this.
+    fn try_grow(&self) -> Result<(), TryReserveError> {
+        // the writer is the only one storing `allocated`.
+        let seg = self.allocated.load(Ordering::Relaxed);
+        if seg == MAX_SEGMENTS {
+            return Err(TryReserveError::CapacityOverflow);
+        }
+        let Some(Ok(layout)) =
+            self.segment_cap(seg).map(Layout::array::<T>)
+        else {
+            return Err(TryReserveError::CapacityOverflow);
+        };
+        let Ok(block) = self.alloc.allocate(layout) else {
+            return Err(TryReserveError::AllocError(layout));
+        };
+        // No element of this segment is published yet, so Relaxed is
+        // enough: the Release store of `len` publishes this pointer too.
+        self.segments[seg]
+            .store(block.cast::<T>().as_ptr(), Ordering::Relaxed);
+        self.allocated.store(seg + 1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Drop for SegmentedGrowLock<T, A, L> {
+    fn drop(&mut self) {
+        let len = self.len();
+        if is_zst::<T>() {
+            // SAFETY: the first `len` ZSTs are initialized.
+            unsafe {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                    NonNull::<T>::dangling().as_ptr(),
+                    len,
+                ));
+            }
+            return;
+        }
+        for seg in 0..self.allocated.load(Ordering::Relaxed) {
+            let start = segment_start(self.shift, seg);
+            // SAFETY: this segment was allocated, so its capacity doesn't
+            // overflow.
+            let cap = unsafe { self.segment_cap(seg).unwrap_unchecked() };
+            let ptr = self.segments[seg].load(Ordering::Relaxed);
+            // SAFETY:
+            // * the first `len - start` elements of the segment (up to its
+            //   capacity) are initialized.
+            // * we allocated this segment with this ptr and this layout.
+            unsafe {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(
+                    ptr,
+                    len.saturating_sub(start).min(cap),
+                ));
+                let layout = Layout::array::<T>(cap).unwrap_unchecked();
+                self.alloc.deallocate(
+                    NonNull::new_unchecked(ptr).cast(),
+                    layout,
+                );
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> ops::Index<usize>
+    for SegmentedGrowLock<T, A, L>
+{
+    type Output = T;
+    #[inline]
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(elem) => elem,
+            None => panic!(
+                "index out of bounds: the len is {} but the index is {index}",
+                self.len()
+            ),
+        }
+    }
+}
+impl<T, A: Allocator + Default, L: WriteLock> Default
+    for SegmentedGrowLock<T, A, L>
+{
+    #[inline]
+    fn default() -> Self {
+        Self::new_in(A::default())
+    }
+}
+impl<T: fmt::Debug, A: Allocator, L: WriteLock> fmt::Debug
+    for SegmentedGrowLock<T, A, L>
+{
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+impl<'a, T, A: Allocator, L: WriteLock> IntoIterator
+    for &'a SegmentedGrowLock<T, A, L>
+{
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, A, L>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+// ------------------------------ iterators -------------------------------
+
+/// Iterator over the published part of each segment of a
+/// [`SegmentedGrowLock`].
+///
+/// This struct is created by [`SegmentedGrowLock::segments`].
+pub struct Segments<
+    'a,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'a SegmentedGrowLock<T, A, L>,
+    seg: usize,
+    /// Index of the first element of `seg`.
+    start: usize,
+    /// `len` of the lock when this iterator was created.
+    len: usize,
+}
+impl<'a, T, A: Allocator, L: WriteLock> Iterator
+    for Segments<'a, T, A, L>
+{
+    type Item = &'a [T];
+    fn next(&mut self) -> Option<&'a [T]> {
+        if self.start >= self.len {
+            return None;
+        }
+        if is_zst::<T>() {
+            self.start = self.len;
+            // SAFETY: the first `len` ZSTs are initialized.
+            return Some(unsafe {
+                slice::from_raw_parts(
+                    NonNull::dangling().as_ptr(),
+                    self.len,
+                )
+            });
+        }
+        // SAFETY: this segment holds published elements, so it was
+        // allocated.
+        let cap =
+            unsafe { self.lock.segment_cap(self.seg).unwrap_unchecked() };
+        let ptr = self.lock.segments[self.seg].load(Ordering::Relaxed);
+        let n = (self.len - self.start).min(cap);
+        self.seg += 1;
+        self.start += cap;
+        // SAFETY: the first `n` elements of this segment are published.
+        Some(unsafe { slice::from_raw_parts(ptr, n) })
+    }
+}
+impl<T, A: Allocator, L: WriteLock> FusedIterator
+    for Segments<'_, T, A, L>
+{
+}
+
+/// Iterator over the published elements of a [`SegmentedGrowLock`].
+///
+/// This struct is created by [`SegmentedGrowLock::iter`].
+pub struct Iter<'a, T, A: Allocator = Global, L: WriteLock = DefaultLock> {
+    segments: Segments<'a, T, A, L>,
+    current: slice::Iter<'a, T>,
+}
+impl<'a, T, A: Allocator, L: WriteLock> Iterator for Iter<'a, T, A, L> {
+    type Item = &'a T;
+    #[inline]
+    fn next(&mut self) -> Option<&'a T> {
+        loop {
+            if let Some(elem) = self.current.next() {
+                return Some(elem);
+            }
+            self.current = self.segments.next()?.iter();
+        }
+    }
+}
+impl<T, A: Allocator, L: WriteLock> FusedIterator for Iter<'_, T, A, L> {}
+
+// -------------------------------- guard ---------------------------------
+
+/// RAII structure used to release the exclusive write access of a
+/// [`SegmentedGrowLock`] when dropped.
+///
+/// This structure is created by the [`write`][write] and
+/// [`try_write`][try_write] method on [`SegmentedGrowLock`]
+///
+/// [write]: SegmentedGrowLock::write
+/// [try_write]: SegmentedGrowLock::try_write
+pub struct SegmentedGrowGuard<
+    'lock,
+    T,
+    A: Allocator = Global,
+    L: WriteLock = DefaultLock,
+> {
+    lock: &'lock SegmentedGrowLock<T, A, L>,
+    /// Released in [`Drop`].
+    permit: WritePermit,
+}
+
+impl<'lock, T, A: Allocator, L: WriteLock>
+    SegmentedGrowGuard<'lock, T, A, L>
+{
+    #[inline]
+    #[must_use]
+    const fn new(
+        lock: &'lock SegmentedGrowLock<T, A, L>,
+        permit: WritePermit,
+    ) -> Self {
+        Self { lock, permit }
+    }
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        // We hold the write lock so writes cannot happen, and the only
+        // thread that stores `len` is this one.
+        self.lock.len.load(Ordering::Relaxed)
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.lock.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.lock.get(index)
+    }
+    /// Appends an element, allocating a new segment if the allocated ones
+    /// are full.
+    ///
+    /// # Panics
+    /// Panics if the new capacity overflows `isize::MAX` bytes.
+    pub fn push(&mut self, value: T) {
+        match self.try_push(value) {
+            Ok(()) => {}
+            Err(e @ TryReserveError::CapacityOverflow) => panic!("{e}"),
+            Err(TryReserveError::AllocError(layout)) => {
+                handle_alloc_error(layout)
+            }
+        }
+    }
+    /// Appends an element, allocating a new segment if the allocated ones
+    /// are full.
+    ///
+    /// # Errors
+    /// Returns an error if a new segment is needed and:
+    /// * its capacity overflows `isize::MAX` bytes
+    /// * memory is exhausted
+    pub fn try_push(&mut self, value: T) -> Result<(), TryReserveError> {
+        let len = self.len();
+        if len == self.capacity() {
+            if is_zst::<T>() {
+                return Err(TryReserveError::CapacityOverflow);
+            }
+            self.lock.try_grow()?;
+        }
+        // SAFETY: `len < capacity`, and we allocated its segment.
+        unsafe {
+            self.lock.slot(len).write(value);
+        }
+        // Release: publishes the element written above (and its segment)
+        // to the readers.
+        self.lock.len.store(len + 1, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Drop
+    for SegmentedGrowGuard<'_, T, A, L>
+{
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: the permit comes from the lock, and it is never used
+        // again.
+        unsafe { self.lock.write_lock.unlock(&self.permit) };
+    }
+}
+
+impl<T, A: Allocator, L: WriteLock> Extend<T>
+    for SegmentedGrowGuard<'_, T, A, L>
+{
+    /// Extends the [`SegmentedGrowLock<T>`] with the contents of an
+    /// iterator.
+    ///
+    /// # Panics
+    /// Panics if the new capacity overflows `isize::MAX` bytes.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for elem in iter {
+            self.push(elem);
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use {
+        super::*,
+        crate::lock::StdLock,
+        std::{
+            sync::{Arc, atomic::AtomicUsize},
+            thread,
+        },
+    };
+
+    #[test]
+    fn locate_index() {
+        // segments of 4, 8, 16, ...
+        assert_eq!(locate(2, 0), (0, 0));
+        assert_eq!(locate(2, 3), (0, 3));
+        assert_eq!(locate(2, 4), (1, 0));
+        assert_eq!(locate(2, 11), (1, 7));
+        assert_eq!(locate(2, 12), (2, 0));
+        assert_eq!(locate(0, 0), (0, 0));
+        assert_eq!(locate(0, 1), (1, 0));
+        assert_eq!(locate(0, 2), (1, 1));
+        assert_eq!(locate(0, 3), (2, 0));
+    }
+
+    #[test]
+    fn grow_past_capacity() {
+        let lock = SegmentedGrowLock::with_capacity(5);
+        assert_eq!(lock.capacity(), 8);
+        let mut guard = lock.write().unwrap();
+        guard.extend(0..8);
+        let first = &lock[0];
+        let last = &lock[7];
+
+        guard.extend(8..100);
+        assert!(lock.capacity() >= 100);
+        assert_eq!(lock.len(), 100);
+        // elements are never moved
+        assert!(ptr::eq(first, &raw const lock[0]));
+        assert!(ptr::eq(last, &raw const lock[7]));
+        assert!(lock.iter().copied().eq(0..100));
+        assert_eq!(lock.get(100), None);
+    }
+
+    #[test]
+    fn lazy_first_segment() {
+        let lock = SegmentedGrowLock::new();
+        assert_eq!(lock.capacity(), 0);
+        lock.write().unwrap().push(String::from("hi"));
+        assert_eq!(lock.capacity(), MIN_SEGMENT_CAP);
+        assert_eq!(lock[0], "hi");
+    }
+
+    #[test]
+    fn drop_elements() {
+        struct AddOnDrop<'a>(&'a AtomicUsize);
+        impl Drop for AddOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let counter = AtomicUsize::new(0);
+        {
+            let lock = SegmentedGrowLock::with_capacity(3);
+            let mut guard = lock.write().unwrap();
+            for _ in 0..30 {
+                guard.push(AddOnDrop(&counter));
+            }
+        }
+        assert_eq!(counter.load(Ordering::Relaxed), 30);
+
+        let lock = SegmentedGrowLock::new();
+        lock.write().unwrap().extend([(), (), ()]);
+        assert_eq!(lock.capacity(), usize::MAX);
+        assert_eq!(lock.iter().count(), 3);
+    }
+
+    #[test]
+    fn read_while_growing() {
+        const LEN: usize = 1000;
+        let lock = Arc::new(SegmentedGrowLock::with_capacity(1));
+        let handle = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let mut guard = lock.write().unwrap();
+                for i in 0..LEN {
+                    guard.push(i);
+                }
+            }
+        });
+        while lock.len() < LEN {
+            for (i, &elem) in lock.iter().enumerate() {
+                assert_eq!(elem, i);
+            }
+        }
+        handle.join().unwrap();
+        assert_eq!(
+            format!("{:?}", lock.iter().take(3).collect::<Vec<_>>()),
+            "[0, 1, 2]"
+        );
+    }
+
+    /// Tests the poisoning, and that it is kept by `with_lock`.
+    #[test]
+    fn poisoning() {
+        let lock = SegmentedGrowLock::new();
+        let _ = thread::scope(|s| {
+            s.spawn(|| {
+                let mut guard = lock.write().unwrap();
+                guard.push('a');
+                panic!("oops!");
+            })
+            .join()
+        });
+
+        assert!(lock.is_poisoned());
+        assert!(matches!(
+            lock.try_write_for(Duration::ZERO),
+            Err(TryLockError::Poisoned(_))
+        ));
+        let lock = lock.with_lock::<StdLock>();
+        assert!(lock.write().is_err());
+        lock.clear_poison();
+        lock.write().unwrap().push('b');
+        assert_eq!(lock.iter().collect::<String>(), "ab");
+    }
+
+    /// Tests that the writers are serialized, and time out.
+    #[test]
+    fn try_write_for() {
+        let lock =
+            SegmentedGrowLock::with_capacity(1).with_lock::<StdLock>();
+        let guard = lock.write().unwrap();
+        assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+        assert!(matches!(
+            lock.try_write_for(Duration::from_millis(10)),
+            Err(TryLockError::WouldBlock)
+        ));
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut guard = lock.try_write_for(Duration::MAX).unwrap();
+                guard.extend(0..10);
+            });
+            thread::sleep(Duration::from_millis(10));
+            drop(guard);
+        });
+        assert!(lock.iter().copied().eq(0..10));
+    }
+}
@@ -0,0 +1,304 @@
+//! [`Serialize`] and [`Deserialize`] impls, with the `serde` feature.
+//!
+//! A [`GrowLock`] is serialized as a sequence of its published elements
+//! (the elements pushed concurrently may or may not be serialized), like
+//! a [`Vec`]. Deserializing it gives a full lock, whose capacity is the
+//! number of elements.
+//!
+//! The capacity can be kept with the [`with_capacity`] format:
+//! ```
+//! use {
+//!     growlock::GrowLock,
+//!     serde::{Deserialize, Serialize},
+//! };
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Snapshot {
+//!     #[serde(with = "growlock::serde::with_capacity")]
+//!     events: GrowLock<u32>,
+//! }
+//!
+//! let events = GrowLock::with_capacity(10);
+//! events.write().unwrap().extend([1, 2, 3]);
+//! let json = serde_json::to_string(&Snapshot { events }).unwrap();
+//! assert_eq!(json, r#"{"events":{"capacity":10,"elements":[1,2,3]}}"#);
+//!
+//! let snapshot: Snapshot = serde_json::from_str(&json).unwrap();
+//! assert_eq!(snapshot.events, [1, 2, 3]);
+//! assert_eq!(snapshot.events.capacity(), 10);
+//! ```
+
+use {
+    crate::{
+        GrowLock,
+        allocator::{Allocator, Global},
+        lock::WriteLock,
+    },
+    ::serde::{Deserialize, Deserializer, Serialize, Serializer, de},
+    alloc::vec::Vec,
+};
+
+impl<T, A, L> Serialize for GrowLock<T, A, L>
+where
+    T: Serialize,
+    A: Allocator,
+    L: WriteLock,
+{
+    #[inline]
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.as_slice())
+    }
+}
+
+impl<'de, T, L> Deserialize<'de> for GrowLock<T, Global, L>
+where
+    T: Deserialize<'de>,
+    L: WriteLock,
+{
+    /// Deserializes a sequence into a full [`GrowLock`].
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let elements = Vec::deserialize(deserializer)?;
+        let capacity = elements.len();
+        from_parts(elements, capacity)
+    }
+}
+
+/// Rebuilds a [`GrowLock`] of `capacity`, failing (instead of aborting)
+/// if it can't be allocated.
+fn from_parts<T, L: WriteLock, E: de::Error>(
+    elements: Vec<T>,
+    capacity: usize,
+) -> Result<GrowLock<T, Global, L>, E> {
+    GrowLock::try_from_vec_with_capacity(elements, capacity)
+        .map(GrowLock::with_lock)
+        .map_err(E::custom)
+}
+
+/// Format of a [`GrowLock`] which keeps its capacity, to be used with
+/// `#[serde(with = "growlock::serde::with_capacity")]`.
+///
+/// The lock is serialized as a struct with the `capacity` and the
+/// published `elements`, and deserialized into a lock with the same
+/// capacity, so it keeps its room for the further pushes.
+///
+/// # Untrusted input
+/// The recorded capacity is allocated upfront, whatever the number of
+/// elements: without a bound, `{"capacity":100000000000,"elements":[]}`
+/// would reserve 100 GB for a `GrowLock<u8>`. So [`deserialize`] rejects
+/// a capacity leaving more than [`DEFAULT_MAX_HEADROOM`] free slots. Use
+/// [`deserialize_with_headroom`] with `deserialize_with` to choose
+/// another bound:
+/// ```
+/// use {
+///     growlock::{GrowLock, serde::with_capacity},
+///     serde::{Deserialize, Deserializer},
+/// };
+///
+/// fn events<'de, D: Deserializer<'de>>(
+///     deserializer: D,
+/// ) -> Result<GrowLock<u32>, D::Error> {
+///     with_capacity::deserialize_with_headroom(deserializer, 1 << 20)
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Snapshot {
+///     #[serde(deserialize_with = "events")]
+///     events: GrowLock<u32>,
+/// }
+///
+/// let json = r#"{"events":{"capacity":1000000,"elements":[1,2,3]}}"#;
+/// let snapshot: Snapshot = serde_json::from_str(json).unwrap();
+/// assert_eq!(snapshot.events.capacity(), 1_000_000);
+/// ```
+///
+/// [`deserialize`]: with_capacity::deserialize
+/// [`DEFAULT_MAX_HEADROOM`]: with_capacity::DEFAULT_MAX_HEADROOM
+/// [`deserialize_with_headroom`]: with_capacity::deserialize_with_headroom
+pub mod with_capacity {
+    use {
+        super::from_parts,
+        crate::{
+            GrowLock,
+            allocator::{Allocator, Global},
+            lock::WriteLock,
+        },
+        ::serde::{
+            Deserialize, Deserializer, Serialize, Serializer, de::Error,
+        },
+        alloc::vec::Vec,
+    };
+
+    /// Maximum number of free slots (the capacity minus the number of
+    /// elements) accepted by [`deserialize`].
+    pub const DEFAULT_MAX_HEADROOM: usize = 1 << 16;
+
+    #[derive(Serialize)]
+    #[serde(rename = "GrowLock")]
+    struct Ser<'a, T> {
+        capacity: usize,
+        elements: &'a [T],
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "GrowLock")]
+    struct De<T> {
+        capacity: usize,
+        elements: Vec<T>,
+    }
+
+    /// Serializes the capacity and the published elements of `lock`.
+    ///
+    /// # Errors
+    /// Returns the errors of `serializer`.
+    pub fn serialize<T, A, L, S>(
+        lock: &GrowLock<T, A, L>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        A: Allocator,
+        L: WriteLock,
+        S: Serializer,
+    {
+        Ser {
+            capacity: lock.capacity(),
+            elements: lock.as_slice(),
+        }
+        .serialize(serializer)
+    }
+
+    /// Deserializes a [`GrowLock`] with the recorded capacity, if it
+    /// leaves at most [`DEFAULT_MAX_HEADROOM`] free slots.
+    ///
+    /// # Errors
+    /// Returns the errors of [`deserialize_with_headroom`].
+    pub fn deserialize<'de, T, L, D>(
+        deserializer: D,
+    ) -> Result<GrowLock<T, Global, L>, D::Error>
+    where
+        T: Deserialize<'de>,
+        L: WriteLock,
+        D: Deserializer<'de>,
+    {
+        deserialize_with_headroom(deserializer, DEFAULT_MAX_HEADROOM)
+    }
+
+    /// Deserializes a [`GrowLock`] with the recorded capacity, if it
+    /// leaves at most `max_headroom` free slots.
+    ///
+    /// # Errors
+    /// Returns the errors of `deserializer`, and an error if:
+    /// * there are more elements than the capacity
+    /// * the capacity leaves more than `max_headroom` free slots
+    /// * the buffer can't be allocated
+    pub fn deserialize_with_headroom<'de, T, L, D>(
+        deserializer: D,
+        max_headroom: usize,
+    ) -> Result<GrowLock<T, Global, L>, D::Error>
+    where
+        T: Deserialize<'de>,
+        L: WriteLock,
+        D: Deserializer<'de>,
+    {
+        let De { capacity, elements } = De::deserialize(deserializer)?;
+        let headroom = capacity.saturating_sub(elements.len());
+        if headroom > max_headroom {
+            return Err(D::Error::custom(format_args!(
+                "the capacity {capacity} leaves {headroom} free slots, \
+                 more than the maximum of {max_headroom}"
+            )));
+        }
+        from_parts(elements, capacity)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use crate::{GrowLock, grow_lock};
+
+    #[test]
+    fn seq() {
+        let lock = grow_lock!(5, [1, 2, 3]);
+        let json = serde_json::to_string(&lock).unwrap();
+        assert_eq!(json, "[1,2,3]");
+
+        let lock: GrowLock<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(lock, [1, 2, 3]);
+        assert!(lock.is_full());
+
+        let lock: GrowLock<u32> = serde_json::from_str("[]").unwrap();
+        assert_eq!(lock.capacity(), 0);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let lock = grow_lock!(5, [String::from("a")]);
+        let mut json = Vec::new();
+        super::with_capacity::serialize(
+            &lock,
+            &mut serde_json::Serializer::new(&mut json),
+        )
+        .unwrap();
+        assert_eq!(json, br#"{"capacity":5,"elements":["a"]}"#);
+
+        let lock: GrowLock<String> = super::with_capacity::deserialize(
+            &mut serde_json::Deserializer::from_slice(&json),
+        )
+        .unwrap();
+        assert_eq!((lock.len(), lock.capacity()), (1, 5));
+        lock.write().unwrap().push(String::from("b"));
+        assert_eq!(lock, ["a", "b"]);
+    }
+
+    #[test]
+    fn with_capacity_invalid() {
+        let de = |json: &str| -> Result<GrowLock<u64>, _> {
+            super::with_capacity::deserialize(
+                &mut serde_json::Deserializer::from_str(json),
+            )
+        };
+        let err = de(r#"{"capacity":1,"elements":[1,2]}"#).unwrap_err();
+        assert!(err.to_string().contains("don't fit"), "{err}");
+        // a capacity overflowing `isize::MAX` fails instead of aborting.
+        let json =
+            format!(r#"{{"capacity":{},"elements":[]}}"#, usize::MAX);
+        assert!(de(&json).is_err());
+    }
+
+    #[test]
+    fn with_capacity_headroom() {
+        use super::with_capacity::{
+            DEFAULT_MAX_HEADROOM, deserialize_with_headroom,
+        };
+
+        let json = |capacity: usize| {
+            format!(r#"{{"capacity":{capacity},"elements":[1,2]}}"#)
+        };
+        let de = |json: &str| -> Result<GrowLock<u8>, _> {
+            super::with_capacity::deserialize(
+                &mut serde_json::Deserializer::from_str(json),
+            )
+        };
+        let max = DEFAULT_MAX_HEADROOM + 2;
+        assert_eq!(de(&json(max)).unwrap().capacity(), max);
+        // a huge capacity is rejected before allocating.
+        let err = de(&json(100_000_000_000)).unwrap_err();
+        assert!(err.to_string().contains("free slots"), "{err}");
+        assert!(de(&json(max + 1)).is_err());
+
+        let de = |json: &str, max_headroom| -> Result<GrowLock<u8>, _> {
+            deserialize_with_headroom(
+                &mut serde_json::Deserializer::from_str(json),
+                max_headroom,
+            )
+        };
+        assert!(de(&json(2), 0).is_ok());
+        assert!(de(&json(3), 0).is_err());
+        assert_eq!(de(&json(max + 1), max).unwrap().capacity(), max + 1);
+    }
+}
@@ -0,0 +1,1016 @@
+//! An append-only array shared between processes.
+//!
+//! A [`SharedGrowLock`] works like a [`GrowLock`](crate::GrowLock) of
+//! [`Pod`] elements, placed in a POSIX shared-memory segment (or any file
+//! mapped by several processes, e.g. a `memfd` inherited by the child
+//! processes). Its header holds the published length and a process-shared
+//! write lock, so the pushes of a process are seen by the others:
+//! * a writer process attaches with [`SharedGrowLock::create`] or
+//!   [`SharedGrowLock::open`], and pushes through a [`SharedGuard`].
+//! * a reader process attaches read-only with [`SharedReader::open`], and
+//!   gets the same lock-free [`as_slice`](SharedReader::as_slice) view as
+//!   a [`GrowLock`](crate::GrowLock) within one process.
+//!
+//! This module is only available with the `shm` feature, on 64-bit Unix
+//! targets: the readers load the 64-bit words of the header from a
+//! read-only mapping, which is only allowed for atomics of up to the
+//! pointer width.
+//!
+//! # Write lock
+//! On Linux, the write lock is a robust, process-shared
+//! `pthread_mutex_t` in the header. If a thread or a process dies while
+//! holding it, the next writer takes it over and poisons the lock, as if
+//! the writer had panicked. The elements it didn't publish are discarded
+//! (the length is only stored after the elements are written), so the
+//! readers never see a torn element. As the mutex must be unlocked by the
+//! thread which locked it, a [`SharedGuard`] is not [`Send`].
+//!
+//! Elsewhere, the write lock is a 32-bit word of the header, holding the
+//! process id of its holder, and the waiting writers yield to the
+//! scheduler. A writer takes the lock over (and poisons it) only if its
+//! holder process doesn't exist anymore: the death of a thread is not
+//! detected, the processes must share the same pid namespace, and if the
+//! pid of a dead holder is reused by another process, the lock is never
+//! taken over.
+//!
+//! # Safety
+//! The readers borrow the elements straight from the mapping of the
+//! segment, which can't be trusted: any process which can open it may
+//! rewrite the published elements under a shared reference, or truncate
+//! the segment so that reading them faults with `SIGBUS`. This is why
+//! attaching to a segment is `unsafe`: while attached, every process must
+//! only modify the segment through a [`SharedGrowLock`].
+//!
+//! # Examples
+//! ```
+//! use growlock::shm::{SharedGrowLock, SharedReader};
+//!
+//! let name = format!("/growlock-doc-{}", std::process::id());
+//! // SAFETY: the segment is only modified through `SharedGrowLock`s.
+//! let lock = unsafe { SharedGrowLock::<u32>::create(&name, 100)? };
+//! lock.write().unwrap().extend_from_slice(&[1, 2]);
+//!
+//! // in another process
+//! // SAFETY: as above.
+//! let reader = unsafe { SharedReader::<u32>::open(&name)? };
+//! assert_eq!(&reader[..], &[1, 2]);
+//! lock.write().unwrap().push(3);
+//! assert_eq!(&reader[..], &[1, 2, 3]);
+//!
+//! // the segment lives until it is unlinked.
+//! SharedGrowLock::<u32>::unlink(&name)?;
+//! # Ok::<(), std::io::Error>(())
+//! ```
+
+#[cfg(not(target_os = "linux"))]
+use std::process;
+#[cfg(target_os = "linux")]
+use std::{cell::UnsafeCell, mem::MaybeUninit};
+
+use {
+    crate::{
+        error::{
+            LengthError, LockResult, PoisonError, TryLockError,
+            TryLockResult,
+        },
+        mmap::{
+            Pod, append, data_offset, file_size, invalid_data, map_file,
+        },
+    },
+    std::{
+        ffi::CString,
+        fmt,
+        fs::File,
+        io,
+        marker::PhantomData,
+        ops,
+        os::fd::{AsFd, BorrowedFd, FromRawFd},
+        ptr::NonNull,
+        slice,
+        sync::atomic::{self, AtomicU32, AtomicU64, Ordering},
+        thread,
+    },
+};
+
+const MAGIC: u64 = u64::from_ne_bytes(*b"GROWSHM\0");
+const VERSION: u32 = 2;
+
+/// Header at the start of the segment.
+#[repr(C)]
+struct Header {
+    /// Stored last (with [`Ordering::Release`]), once the header is
+    /// initialized.
+    magic: AtomicU64,
+    version: u32,
+    elem_size: u32,
+    elem_align: u32,
+    lock: WriteMutex,
+    capacity: u64,
+    /// Number of published elements, stored with [`Ordering::Release`]
+    /// after the elements are written.
+    len: AtomicU64,
+    poisoned: AtomicU32,
+    _reserved: u32,
+}
+
+/// Robust, process-shared write lock of a segment.
+#[cfg(target_os = "linux")]
+#[repr(transparent)]
+struct WriteMutex(UnsafeCell<libc::pthread_mutex_t>);
+
+#[cfg(target_os = "linux")]
+impl WriteMutex {
+    const fn new() -> Self {
+        Self(UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER))
+    }
+    /// Initializes the mutex in place, as robust and process-shared.
+    ///
+    /// # Safety
+    /// The mutex must not be shared with anyone yet.
+    unsafe fn init(&self) -> io::Result<()> {
+        let mut attr = MaybeUninit::<libc::pthread_mutexattr_t>::uninit();
+        // SAFETY: `attr` is initialized by `pthread_mutexattr_init`
+        // before being used, and destroyed afterwards. The caller ensures
+        // that no one uses the mutex meanwhile.
+        unsafe {
+            check(libc::pthread_mutexattr_init(attr.as_mut_ptr()))?;
+            let res = check(libc::pthread_mutexattr_setpshared(
+                attr.as_mut_ptr(),
+                libc::PTHREAD_PROCESS_SHARED,
+            ))
+            .and_then(|()| {
+                check(libc::pthread_mutexattr_setrobust(
+                    attr.as_mut_ptr(),
+                    libc::PTHREAD_MUTEX_ROBUST,
+                ))
+            })
+            .and_then(|()| {
+                check(libc::pthread_mutex_init(
+                    self.0.get(),
+                    attr.as_ptr(),
+                ))
+            });
+            libc::pthread_mutexattr_destroy(attr.as_mut_ptr());
+            res
+        }
+    }
+    /// Acquires the lock, returning `true` if its previous holder died
+    /// while holding it.
+    fn lock(&self) -> bool {
+        // SAFETY: the mutex is initialized, and it stays in place.
+        let res = unsafe { libc::pthread_mutex_lock(self.0.get()) };
+        self.acquired(res)
+    }
+    /// Acquires the lock if it is free, returning `true` if its previous
+    /// holder died while holding it.
+    fn try_lock(&self) -> Option<bool> {
+        // SAFETY: the mutex is initialized, and it stays in place.
+        let res = unsafe { libc::pthread_mutex_trylock(self.0.get()) };
+        (res != libc::EBUSY).then(|| self.acquired(res))
+    }
+    /// Handles the result `res` of a successful locking.
+    fn acquired(&self, res: libc::c_int) -> bool {
+        match res {
+            0 => false,
+            libc::EOWNERDEAD => {
+                // SAFETY: we hold the mutex. The elements are never torn,
+                // so the state it protects is consistent.
+                unsafe { libc::pthread_mutex_consistent(self.0.get()) };
+                true
+            }
+            _ => panic!(
+                "failed to lock the mutex: {}",
+                io::Error::from_raw_os_error(res)
+            ),
+        }
+    }
+    /// Releases the lock.
+    ///
+    /// # Safety
+    /// The current thread must hold the lock.
+    unsafe fn unlock(&self) {
+        // SAFETY: the caller holds the mutex.
+        unsafe { libc::pthread_mutex_unlock(self.0.get()) };
+    }
+}
+
+/// Converts the result of a `pthread` function.
+#[cfg(target_os = "linux")]
+fn check(res: libc::c_int) -> io::Result<()> {
+    if res == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(res))
+    }
+}
+
+/// Write lock of a segment, holding the process id of its holder (`0` if
+/// it is free).
+#[cfg(not(target_os = "linux"))]
+struct WriteMutex(AtomicU32);
+
+#[cfg(not(target_os = "linux"))]
+impl WriteMutex {
+    const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+    #[allow(clippy::unnecessary_wraps, clippy::unused_self)]
+    unsafe fn init(&self) -> io::Result<()> {
+        Ok(())
+    }
+    /// Acquires the lock, returning `true` if it was taken over from a
+    /// dead process.
+    fn lock(&self) -> bool {
+        loop {
+            if let Some(died) = self.try_lock() {
+                return died;
+            }
+            // there are no process-shared futexes to sleep on.
+            thread::yield_now();
+        }
+    }
+    /// Acquires the lock if it is free, or if its holder process is dead,
+    /// returning `true` in the latter case.
+    fn try_lock(&self) -> Option<bool> {
+        let holder = self.0.load(Ordering::Relaxed);
+        if holder != 0 && is_alive(holder) {
+            return None;
+        }
+        self.0
+            .compare_exchange(
+                holder,
+                process::id(),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .ok()
+            .map(|_| holder != 0)
+    }
+    /// Releases the lock.
+    ///
+    /// # Safety
+    /// The current thread must hold the lock.
+    unsafe fn unlock(&self) {
+        self.0.store(0, Ordering::Release);
+    }
+}
+
+/// Converts the name of a shared-memory segment (e.g. `"/events"`).
+fn shm_name(name: &str) -> io::Result<CString> {
+    CString::new(name).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the name contains a nul byte",
+        )
+    })
+}
+
+/// Opens the shared-memory segment `name` with `flags`.
+fn shm_open(name: &str, flags: libc::c_int) -> io::Result<File> {
+    let name = shm_name(name)?;
+    // SAFETY: `name` is a nul-terminated string.
+    let fd = unsafe { libc::shm_open(name.as_ptr(), flags, 0o600) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` is a new file descriptor, owned by no one else.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+/// Shared mapping of a segment, read-only or not.
+struct Segment<T> {
+    map: NonNull<u8>,
+    map_len: usize,
+    capacity: usize,
+    file: File,
+    _marker: PhantomData<T>,
+}
+
+// SAFETY: the mapping is owned by the `Segment`, and `T: Send`.
+unsafe impl<T: Pod> Send for Segment<T> {}
+// SAFETY: the segment is only written under the write lock, in the slots
+// past the published length, and in the atomics and the write lock of the
+// header.
+unsafe impl<T: Pod> Sync for Segment<T> {}
+
+impl<T: Pod> Segment<T> {
+    /// Initializes `file` (which must be empty) with an empty array of
+    /// `capacity`.
+    fn create(file: File, capacity: usize) -> io::Result<Self> {
+        let size = file_size::<Header, T>(capacity)?;
+        file.set_len(size as u64)?;
+        let map = map_file(&file, size, true)?;
+        let header = Header {
+            magic: AtomicU64::new(0),
+            version: VERSION,
+            // `Segment::open` checks them against `T`.
+            #[allow(clippy::cast_possible_truncation)]
+            elem_size: size_of::<T>() as u32,
+            #[allow(clippy::cast_possible_truncation)]
+            elem_align: align_of::<T>() as u32,
+            lock: WriteMutex::new(),
+            capacity: capacity as u64,
+            len: AtomicU64::new(0),
+            poisoned: AtomicU32::new(0),
+            _reserved: 0,
+        };
+        // SAFETY: the mapping starts with room for the header, and it is
+        // page-aligned. No one reads it before the magic number is stored.
+        unsafe { map.cast::<Header>().write(header) };
+        let segment = Self {
+            map,
+            map_len: size,
+            capacity,
+            file,
+            _marker: PhantomData,
+        };
+        // SAFETY: no one reads the header before the magic number is
+        // stored.
+        unsafe { segment.header().lock.init() }?;
+        segment.header().magic.store(MAGIC, Ordering::Release);
+        Ok(segment)
+    }
+    /// Maps the array initialized in `file`, checking its header.
+    fn open(file: File, writable: bool) -> io::Result<Self> {
+        let file_len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| invalid_data("the segment is too large"))?;
+        if file_len < size_of::<Header>() {
+            return Err(invalid_data(
+                "the segment is too short for a header",
+            ));
+        }
+        let mut segment = Self {
+            map: map_file(&file, file_len, writable)?,
+            map_len: file_len,
+            capacity: 0,
+            file,
+            _marker: PhantomData,
+        };
+        let header = segment.header();
+        // a reader's mapping is read-only, where only `Relaxed` atomic
+        // loads are allowed: the fence gives them the `Acquire` ordering.
+        let magic = header.magic.load(Ordering::Relaxed);
+        atomic::fence(Ordering::Acquire);
+        if magic != MAGIC {
+            return Err(invalid_data("not a `SharedGrowLock` segment"));
+        }
+        if header.version != VERSION {
+            return Err(invalid_data("unsupported version"));
+        }
+        if header.elem_size as usize != size_of::<T>()
+            || header.elem_align as usize != align_of::<T>()
+        {
+            return Err(invalid_data(
+                "the segment holds elements of another type",
+            ));
+        }
+        let capacity = usize::try_from(header.capacity)
+            .ok()
+            .filter(|&capacity| {
+                file_size::<Header, T>(capacity)
+                    .is_ok_and(|size| size <= file_len)
+            })
+            .ok_or_else(|| invalid_data("the segment is too short"))?;
+        segment.capacity = capacity;
+        Ok(segment)
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping starts with the header, and it is
+        // page-aligned. Only its atomics and its write lock are mutated,
+        // and only through a
+        // writable mapping: a read-only one is only loaded with
+        // `Ordering::Relaxed`, which is allowed on read-only memory for
+        // atomics of up to the pointer width.
+        unsafe { self.map.cast().as_ref() }
+    }
+    fn data(&self) -> *mut T {
+        // SAFETY: the buffer is inside the mapping.
+        unsafe { self.map.as_ptr().add(data_offset::<Header, T>()).cast() }
+    }
+    fn len(&self) -> usize {
+        // `Relaxed` and a fence, as in `Segment::open`.
+        let len = self.header().len.load(Ordering::Relaxed);
+        atomic::fence(Ordering::Acquire);
+        // another process may have corrupted it.
+        usize::try_from(len)
+            .map_or(self.capacity, |len| len.min(self.capacity))
+    }
+    fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` elements are initialized (any bit
+        // pattern is a valid `T`), and they are never mutated.
+        unsafe { slice::from_raw_parts(self.data(), self.len()) }
+    }
+}
+
+impl<T> Drop for Segment<T> {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is not used anymore. The elements are
+        // `Copy`, so nothing needs to be dropped.
+        unsafe { libc::munmap(self.map.as_ptr().cast(), self.map_len) };
+    }
+}
+
+/// An append-only array of [`Pod`] elements in shared memory, with the
+/// write access, see the [module docs](self).
+pub struct SharedGrowLock<T: Pod> {
+    segment: Segment<T>,
+}
+
+impl<T: Pod> SharedGrowLock<T> {
+    /// Creates the shared-memory segment `name` (e.g. `"/events"`),
+    /// holding an empty [`SharedGrowLock`] of `capacity` elements.
+    ///
+    /// The segment lives until it is unlinked, see
+    /// [`SharedGrowLock::unlink`].
+    ///
+    /// # Errors
+    /// Returns an error if the segment already exists, if it can't be
+    /// created or mapped, or if its size overflows `isize::MAX`.
+    ///
+    /// # Safety
+    /// While the returned value is alive, the segment must not be modified
+    /// or truncated other than through a [`SharedGrowLock`], by any
+    /// process, see the [module docs](self#safety).
+    pub unsafe fn create(name: &str, capacity: usize) -> io::Result<Self> {
+        let file =
+            shm_open(name, libc::O_CREAT | libc::O_EXCL | libc::O_RDWR)?;
+        // SAFETY: the caller upholds the contract for the segment.
+        unsafe { Self::create_in(file, capacity) }.inspect_err(|_| {
+            let _ = Self::unlink(name);
+        })
+    }
+    /// Creates a [`SharedGrowLock`] of `capacity` elements in an
+    /// anonymous `memfd`, which is shared with the child processes
+    /// inheriting it (see [`SharedGrowLock::from_file`]).
+    ///
+    /// This is only available on Linux.
+    ///
+    /// Unlike the other constructors, this is safe: no other process can
+    /// reach the `memfd` until it is shared, and the processes it is
+    /// shared with attach with the `unsafe` [`SharedGrowLock::from_file`]
+    /// or [`SharedReader::from_file`].
+    ///
+    /// # Errors
+    /// Returns an error if the `memfd` can't be created or mapped, or if
+    /// its size overflows `isize::MAX`.
+    #[cfg(target_os = "linux")]
+    pub fn anonymous(capacity: usize) -> io::Result<Self> {
+        // SAFETY: the name is a nul-terminated string.
+        let fd = unsafe { libc::memfd_create(c"growlock".as_ptr(), 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a new file descriptor, owned by no one else, so
+        // no one else can modify it.
+        unsafe { Self::create_in(File::from_raw_fd(fd), capacity) }
+    }
+    /// Creates a [`SharedGrowLock`] of `capacity` elements in `file`,
+    /// which must be empty.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be resized or mapped, or if its
+    /// size overflows `isize::MAX`.
+    ///
+    /// # Safety
+    /// While the returned value is alive, the file must not be modified
+    /// or truncated other than through a [`SharedGrowLock`], by any
+    /// process, see the [module docs](self#safety).
+    pub unsafe fn create_in(
+        file: File,
+        capacity: usize,
+    ) -> io::Result<Self> {
+        if file.metadata()?.len() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "the file is not empty",
+            ));
+        }
+        Segment::create(file, capacity).map(|segment| Self { segment })
+    }
+    /// Attaches to the [`SharedGrowLock`] in the shared-memory segment
+    /// `name`, with the write access.
+    ///
+    /// # Errors
+    /// Returns an error if the segment can't be opened or mapped, or if
+    /// it doesn't hold a [`SharedGrowLock<T>`].
+    ///
+    /// # Safety
+    /// While the returned value is alive, the segment must not be modified
+    /// or truncated other than through a [`SharedGrowLock`], by any
+    /// process, see the [module docs](self#safety).
+    pub unsafe fn open(name: &str) -> io::Result<Self> {
+        // SAFETY: the caller upholds the contract for the segment.
+        unsafe { Self::from_file(shm_open(name, libc::O_RDWR)?) }
+    }
+    /// Attaches to the [`SharedGrowLock`] in `file` (e.g. a `memfd`
+    /// inherited from the parent process), with the write access.
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be mapped, or if it doesn't hold
+    /// a [`SharedGrowLock<T>`].
+    ///
+    /// # Safety
+    /// While the returned value is alive, the file must not be modified
+    /// or truncated other than through a [`SharedGrowLock`], by any
+    /// process, see the [module docs](self#safety).
+    pub unsafe fn from_file(file: File) -> io::Result<Self> {
+        Segment::open(file, true).map(|segment| Self { segment })
+    }
+    /// Removes the shared-memory segment `name`. The processes attached
+    /// to it keep it until they detach.
+    ///
+    /// # Errors
+    /// Returns an error if the segment can't be removed.
+    pub fn unlink(name: &str) -> io::Result<()> {
+        let name = shm_name(name)?;
+        // SAFETY: `name` is a nul-terminated string.
+        if unsafe { libc::shm_unlink(name.as_ptr()) } == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.segment.len()
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.segment.capacity
+    }
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self.segment.as_slice()
+    }
+    /// Locks the [`SharedGrowLock`] for writes, blocking the current
+    /// thread until it can be acquired from the other threads and
+    /// processes.
+    ///
+    /// # Errors
+    /// Returns an error if the lock is poisoned, i.e. a writer panicked or
+    /// its process died while holding it.
+    pub fn write(&self) -> LockResult<SharedGuard<'_, T>> {
+        let died = self.segment.header().lock.lock();
+        self.check_poison(self.guard(died))
+    }
+    /// Attempts to lock the [`SharedGrowLock`] for writes, without
+    /// blocking.
+    ///
+    /// # Errors
+    /// Returns an error if the lock is held by another writer, or if it
+    /// is poisoned (it is taken over from a dead holder as in
+    /// [`SharedGrowLock::write`]).
+    pub fn try_write(&self) -> TryLockResult<SharedGuard<'_, T>> {
+        let died = self
+            .segment
+            .header()
+            .lock
+            .try_lock()
+            .ok_or(TryLockError::WouldBlock)?;
+        Ok(self.check_poison(self.guard(died))?)
+    }
+    /// Returns the guard of the write lock, which the caller holds,
+    /// poisoning the lock if its previous holder `died`.
+    fn guard(&self, died: bool) -> SharedGuard<'_, T> {
+        if died {
+            self.segment.header().poisoned.store(1, Ordering::Relaxed);
+        }
+        SharedGuard {
+            lock: self,
+            panicking: thread::panicking(),
+            _not_send: PhantomData,
+        }
+    }
+    fn check_poison<G>(&self, guard: G) -> LockResult<G> {
+        if self.is_poisoned() {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+    /// Returns `true` if a writer panicked, or its process died, while
+    /// holding the write lock.
+    #[inline]
+    #[must_use]
+    pub fn is_poisoned(&self) -> bool {
+        self.segment.header().poisoned.load(Ordering::Relaxed) != 0
+    }
+    /// Clears the poisoning of the write lock.
+    #[inline]
+    pub fn clear_poison(&self) {
+        self.segment.header().poisoned.store(0, Ordering::Relaxed);
+    }
+}
+
+impl<T: Pod> ops::Deref for SharedGrowLock<T> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+impl<T: Pod + fmt::Debug> fmt::Debug for SharedGrowLock<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+impl<T: Pod> AsFd for SharedGrowLock<T> {
+    /// The file descriptor of the segment, to be inherited by a child
+    /// process (after clearing its `FD_CLOEXEC` flag).
+    #[inline]
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.segment.file.as_fd()
+    }
+}
+
+/// RAII structure giving the write access of a [`SharedGrowLock`],
+/// created by [`SharedGrowLock::write`].
+///
+/// The pushed elements are published at once to the readers of every
+/// process.
+#[must_use = "if unused the SharedGrowLock will immediately unlock"]
+pub struct SharedGuard<'lock, T: Pod> {
+    lock: &'lock SharedGrowLock<T>,
+    /// Whether the thread was already panicking when it took the lock.
+    panicking: bool,
+    /// The write lock must be released by the thread which took it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T: Pod> SharedGuard<'_, T> {
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self.lock.as_slice()
+    }
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.lock.len()
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.lock.capacity()
+    }
+    /// # Panics
+    /// Panics if `self.is_full()`.
+    #[inline]
+    pub fn push(&mut self, value: T) {
+        self.extend_from_slice(&[value]);
+    }
+    /// # Errors
+    /// Returns an error if `self.is_full()`.
+    #[inline]
+    pub fn try_push(&mut self, value: T) -> Result<(), LengthError> {
+        self.try_extend_from_slice(&[value])
+    }
+    /// # Panics
+    /// Panics if the elements don't fit in the remaining capacity.
+    #[inline]
+    pub fn extend_from_slice(&mut self, values: &[T]) {
+        assert!(
+            self.try_extend_from_slice(values).is_ok(),
+            "length overflow"
+        );
+    }
+    /// Appends all the elements of `values`, or none of them.
+    ///
+    /// # Errors
+    /// Returns an error if the elements don't fit in the remaining
+    /// capacity.
+    pub fn try_extend_from_slice(
+        &mut self,
+        values: &[T],
+    ) -> Result<(), LengthError> {
+        let segment = &self.lock.segment;
+        // SAFETY: the buffer holds `capacity` elements, and we hold the
+        // write lock.
+        let len = unsafe {
+            append(segment.data(), self.len(), self.capacity(), values)
+        }?;
+        segment.header().len.store(len as u64, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl<T: Pod> ops::Deref for SharedGuard<'_, T> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T: Pod> Drop for SharedGuard<'_, T> {
+    fn drop(&mut self) {
+        // a guard taken while unwinding (e.g. in a `Drop`) only poisons
+        // if it panics again.
+        if !self.panicking && thread::panicking() {
+            let header = self.lock.segment.header();
+            header.poisoned.store(1, Ordering::Relaxed);
+        }
+        // SAFETY: this thread took the lock when creating the guard.
+        unsafe { self.lock.segment.header().lock.unlock() };
+    }
+}
+
+/// A read-only view of a [`SharedGrowLock`], from any process.
+///
+/// Reading never takes a lock: the elements published by the writers are
+/// seen as soon as they are pushed.
+pub struct SharedReader<T: Pod> {
+    segment: Segment<T>,
+}
+
+impl<T: Pod> SharedReader<T> {
+    /// Attaches read-only to the [`SharedGrowLock`] in the shared-memory
+    /// segment `name`.
+    ///
+    /// # Errors
+    /// Returns an error if the segment can't be opened or mapped, or if
+    /// it doesn't hold a [`SharedGrowLock<T>`].
+    ///
+    /// # Safety
+    /// While the returned value is alive, the segment must not be modified
+    /// or truncated other than through a [`SharedGrowLock`], by any
+    /// process, see the [module docs](self#safety).
+    pub unsafe fn open(name: &str) -> io::Result<Self> {
+        // SAFETY: the caller upholds the contract for the segment.
+        unsafe { Self::from_file(shm_open(name, libc::O_RDONLY)?) }
+    }
+    /// Attaches read-only to the [`SharedGrowLock`] in `file` (e.g. a
+    /// `memfd` inherited from the parent process).
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be mapped, or if it doesn't hold
+    /// a [`SharedGrowLock<T>`].
+    ///
+    /// # Safety
+    /// While the returned value is alive, the file must not be modified
+    /// or truncated other than through a [`SharedGrowLock`], by any
+    /// process, see the [module docs](self#safety).
+    pub unsafe fn from_file(file: File) -> io::Result<Self> {
+        Segment::open(file, false).map(|segment| Self { segment })
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.segment.len()
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    #[inline]
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+    #[inline]
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        self.segment.capacity
+    }
+    #[inline]
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        self.segment.as_slice()
+    }
+}
+
+impl<T: Pod> ops::Deref for SharedReader<T> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+impl<T: Pod + fmt::Debug> fmt::Debug for SharedReader<T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// Returns `false` if the process `pid` doesn't exist anymore.
+#[cfg(not(target_os = "linux"))]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: the signal `0` only checks that the process exists.
+    let res = unsafe { libc::kill(pid, 0) };
+    res == 0
+        || io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{Pod, SharedGrowLock, SharedReader},
+        crate::{
+            error::TryLockError,
+            mmap::tests::{PUSHES, THREADS, TempName, push_concurrently},
+        },
+        std::{
+            io::{self, ErrorKind},
+            thread,
+        },
+    };
+
+    fn temp_name() -> TempName {
+        TempName::new("/growlock-shm", |name| {
+            let _ = SharedGrowLock::<u8>::unlink(name);
+        })
+    }
+
+    fn create<T: Pod>(
+        name: &TempName,
+        capacity: usize,
+    ) -> io::Result<SharedGrowLock<T>> {
+        // SAFETY: the tests only modify the segments through
+        // `SharedGrowLock`s (or while no one is attached).
+        unsafe { SharedGrowLock::create(&name.0, capacity) }
+    }
+    fn open<T: Pod>(name: &TempName) -> io::Result<SharedGrowLock<T>> {
+        // SAFETY: see `create`.
+        unsafe { SharedGrowLock::open(&name.0) }
+    }
+    fn open_reader<T: Pod>(
+        name: &TempName,
+    ) -> io::Result<SharedReader<T>> {
+        // SAFETY: see `create`.
+        unsafe { SharedReader::open(&name.0) }
+    }
+
+    #[test]
+    fn attach() {
+        let name = temp_name();
+        let lock = create::<u64>(&name, 4).unwrap();
+        let err = create::<u64>(&name, 4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+
+        let reader = open_reader::<u64>(&name).unwrap();
+        let writer = open::<u64>(&name).unwrap();
+        assert!(reader.is_empty());
+        lock.write().unwrap().extend_from_slice(&[1, 2]);
+        writer.write().unwrap().push(3);
+        assert_eq!(&reader[..], &[1, 2, 3]);
+        assert_eq!(&lock[..], &[1, 2, 3]);
+
+        let mut guard = lock.write().unwrap();
+        assert!(writer.try_write().is_err());
+        guard.push(4);
+        assert!(guard.try_push(5).is_err());
+        drop(guard);
+        assert!(reader.is_full());
+
+        let err = open_reader::<u32>(&name).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn anonymous() {
+        let lock = SharedGrowLock::<[u8; 2]>::anonymous(3).unwrap();
+        let file = std::fs::File::from(
+            std::os::fd::AsFd::as_fd(&lock)
+                .try_clone_to_owned()
+                .unwrap(),
+        );
+        // SAFETY: the `memfd` is only modified through `lock`.
+        let reader =
+            unsafe { SharedReader::<[u8; 2]>::from_file(file) }.unwrap();
+        lock.write().unwrap().push([1, 2]);
+        assert_eq!(&reader[..], &[[1, 2]]);
+    }
+
+    #[test]
+    fn dead_holder() {
+        let name = temp_name();
+        let lock = create::<u32>(&name, 4).unwrap();
+        lock.write().unwrap().push(1);
+
+        // the lock is taken over, and poisoned.
+        hold_and_die(&lock);
+        let Err(TryLockError::Poisoned(poisoned)) = lock.try_write()
+        else {
+            panic!("the lock should be poisoned");
+        };
+        let mut guard = poisoned.into_inner();
+        guard.push(2);
+        drop(guard);
+        assert!(lock.is_poisoned());
+        lock.clear_poison();
+
+        hold_and_die(&lock);
+        assert!(lock.write().is_err());
+        lock.clear_poison();
+        lock.write().unwrap().push(3);
+        assert_eq!(&lock[..], &[1, 2, 3]);
+    }
+
+    /// Leaves the write lock held by a thread which exited.
+    #[cfg(target_os = "linux")]
+    fn hold_and_die(lock: &SharedGrowLock<u32>) {
+        thread::scope(|s| {
+            s.spawn(|| std::mem::forget(lock.write().unwrap()));
+        });
+    }
+    /// Leaves the write lock held by a process which exited.
+    #[cfg(not(target_os = "linux"))]
+    fn hold_and_die(lock: &SharedGrowLock<u32>) {
+        let mut child =
+            std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+        let header = lock.segment.header();
+        header
+            .lock
+            .0
+            .store(pid, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    #[test]
+    fn poison() {
+        let name = temp_name();
+        let lock = create::<u8>(&name, 4).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| {
+                let _guard = lock.write().unwrap();
+                panic!();
+            })
+            .join()
+            .unwrap_err();
+        });
+        assert!(lock.write().is_err());
+        assert!(open::<u8>(&name).unwrap().is_poisoned());
+    }
+
+    /// A guard taken while unwinding doesn't poison the lock
+    #[test]
+    fn write_while_panicking() {
+        struct PushOnDrop<'a>(&'a SharedGrowLock<u8>);
+        impl Drop for PushOnDrop<'_> {
+            fn drop(&mut self) {
+                self.0.write().unwrap().push(1);
+            }
+        }
+
+        let name = temp_name();
+        let lock = create::<u8>(&name, 4).unwrap();
+        thread::scope(|s| {
+            s.spawn(|| {
+                let _push = PushOnDrop(&lock);
+                panic!();
+            })
+            .join()
+            .unwrap_err();
+        });
+        assert!(!lock.is_poisoned());
+        assert_eq!(&lock[..], &[1]);
+    }
+
+    #[test]
+    fn concurrent() {
+        let name = temp_name();
+        let lock = create::<usize>(&name, THREADS * PUSHES).unwrap();
+        let reader = open_reader::<usize>(&name).unwrap();
+        // every thread attaches on its own, like a process.
+        let elements = push_concurrently(
+            || open::<usize>(&name).unwrap(),
+            |lock, n| lock.write().unwrap().push(n),
+            || reader.to_vec(),
+        );
+        assert_eq!(lock[..], elements);
+    }
+}
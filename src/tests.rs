@@ -3,15 +3,25 @@
 // > instantly dropped.
 
 use {
-    crate::{GrowLock, cap::Cap, grow_lock},
+    crate::{
+        GrowLock,
+        cap::Cap,
+        error::{FromVecError, TryReserveError},
+        ext::VecExt,
+        grow_lock,
+        guard::OwnedGrowGuard,
+        lock::{StdLock, WriteLock},
+    },
     std::{
-        alloc::System,
+        alloc::{GlobalAlloc, Layout, System},
+        cell::Cell,
+        iter, ptr,
         sync::{
-            Arc,
+            Arc, TryLockError,
             atomic::{AtomicUsize, Ordering},
         },
         thread,
-        time::Duration,
+        time::{Duration, Instant},
     },
 };
 
@@ -23,6 +33,38 @@ impl Drop for AddOnDrop<'_> {
     }
 }
 
+/// Global allocator counting the bytes allocated by each thread, to find
+/// leaks
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATED: Cell<isize> = const { Cell::new(0) };
+}
+
+// SAFETY: every method forwards to `System`.
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.with(|n| n.set(n.get() + layout.size().cast_signed()));
+        // SAFETY: same contract as `GlobalAlloc::alloc`.
+        unsafe { System.alloc(layout) }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.with(|n| n.set(n.get() - layout.size().cast_signed()));
+        // SAFETY: same contract as `GlobalAlloc::dealloc`.
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Returns the bytes allocated by `f` on this thread and not freed.
+fn leaked(f: impl FnOnce()) -> isize {
+    let before = ALLOCATED.with(Cell::get);
+    f();
+    ALLOCATED.with(Cell::get) - before
+}
+
 // ------------------- constructors -------------------
 
 /// Drop with different capacities, constructors and types that implements
@@ -71,6 +113,76 @@ fn from_vec() {
     assert_eq!(&lock[..], &[1, 2, 3, 4, 5]);
 }
 
+#[test]
+fn from_parts() {
+    let (ptr, len, cap) = grow_lock!(5, [1, 2, 3]).into_parts();
+    // SAFETY: the parts come from a `GrowLock<i32>`.
+    let lock = unsafe { GrowLock::<i32>::from_parts(ptr, len, cap) };
+    assert_eq!((&lock[..], lock.capacity()), (&[1, 2, 3][..], 5));
+
+    let (ptr, len, cap) = lock.into_raw_parts();
+    // SAFETY: the parts come from a `GrowLock<i32>`.
+    let lock = unsafe { GrowLock::<i32>::from_raw_parts(ptr, len, cap) };
+    assert_eq!((&lock[..], lock.capacity()), (&[1, 2, 3][..], 5));
+}
+
+#[test]
+fn try_from_vec_with_capacity() {
+    let mut vec = Vec::with_capacity(10);
+    vec.extend([String::from("a"), String::from("b")]);
+    // shrinks
+    let lock = GrowLock::try_from_vec_with_capacity(vec, 3).unwrap();
+    assert_eq!(lock, ["a", "b"]);
+    assert_eq!(lock.capacity(), 3);
+    // grows
+    let lock =
+        GrowLock::try_from_vec_with_capacity(Vec::from(lock), 6).unwrap();
+    assert_eq!(lock, ["a", "b"]);
+    assert_eq!(lock.capacity(), 6);
+    lock.write().unwrap().push(String::from("c"));
+    // full
+    let lock =
+        GrowLock::try_from_vec_with_capacity(Vec::from(lock), 3).unwrap();
+    assert!(lock.is_full());
+
+    assert_eq!(
+        GrowLock::try_from_vec_with_capacity(vec![1, 2], 1),
+        Err(FromVecError::TooManyElements {
+            len: 2,
+            capacity: 1
+        })
+    );
+    assert_eq!(
+        GrowLock::try_from_vec_with_capacity(vec![1u16], usize::MAX),
+        Err(FromVecError::Reserve(TryReserveError::CapacityOverflow))
+    );
+    let lock =
+        GrowLock::try_from_vec_with_capacity(vec![(), ()], 2).unwrap();
+    assert_eq!((lock.len(), lock.capacity()), (2, usize::MAX));
+}
+
+#[test]
+fn from_boxed_slice() {
+    let boxed: Box<[_]> = Box::new([1, 2, 3]);
+    let ptr = boxed.as_ptr();
+    let lock = GrowLock::from_boxed_slice(boxed);
+    assert_eq!(lock.as_ptr(), ptr);
+    assert_eq!((&lock[..], lock.capacity()), (&[1, 2, 3][..], 3));
+    assert!(lock.write().unwrap().try_push(4).is_err());
+
+    let lock = GrowLock::<u8>::from(Box::<[u8]>::default());
+    assert_eq!(lock.capacity(), 0);
+}
+
+#[test]
+fn into_growlock_with_spare() {
+    let lock = vec![1, 2].into_growlock_with_spare(3);
+    assert_eq!(&lock[..], &[1, 2]);
+    assert!(lock.capacity() >= 5);
+    lock.write().unwrap().extend([3, 4, 5]);
+    assert_eq!(lock, [1, 2, 3, 4, 5]);
+}
+
 // ------------------- macro init -------------------
 
 #[test]
@@ -168,6 +280,19 @@ fn push_overflow() {
         guard.push(i);
     }
 }
+/// The references returned by `push_get` outlive the guard
+#[test]
+fn push_get() {
+    let lock = grow_lock!(3, [String::from("a")]);
+    let mut guard = lock.write().unwrap();
+    let (i, b) = guard.push_get(String::from("b"));
+    let (j, c) = guard.try_push_get(String::from("c")).unwrap();
+    assert!(guard.try_push_get(String::from("d")).is_err());
+    drop(guard);
+    assert_eq!((i, b.as_str()), (1, "b"));
+    assert_eq!((j, c.as_str()), (2, "c"));
+    assert!(ptr::eq(b, lock.get(1).unwrap()));
+}
 /// `try_push` should return an error on length overflow
 #[test]
 fn try_push_overflow() {
@@ -199,6 +324,212 @@ fn init_drop_on_panic() {
     assert_eq!(counter.load(Ordering::Relaxed), 11);
 }
 
+// ------------------- extend_from_slice -------------------
+#[test]
+fn extend_from_slice() {
+    let lock = grow_lock!(5, [String::from("a")]);
+    let mut guard = lock.write().unwrap();
+    guard.extend_from_slice(&[String::from("b"), String::from("c")]);
+    assert!(
+        guard
+            .try_extend_from_slice(&vec![String::new(); 3])
+            .is_err()
+    );
+    assert_eq!(guard.len(), 3);
+    guard.extend_from_slice(&[]);
+    assert!(guard.try_extend_from_slice(&[String::from("d")]).is_ok());
+    drop(guard);
+    assert_eq!(&lock[..], &["a", "b", "c", "d"]);
+}
+
+#[test]
+fn extend_from_copy_slice() {
+    let lock = grow_lock!(5, [1]);
+    let mut guard = lock.write().unwrap();
+    guard.extend_from_copy_slice(&[2, 3]);
+    assert!(guard.try_extend_from_copy_slice(&[0; 3]).is_err());
+    assert_eq!(&guard[..], &[1, 2, 3]);
+    assert!(guard.try_extend_from_copy_slice(&[4, 5]).is_ok());
+    assert!(guard.is_full());
+    drop(guard);
+    assert_eq!(&lock[..], &[1, 2, 3, 4, 5]);
+
+    let lock = GrowLock::with_capacity(0);
+    lock.write().unwrap().extend_from_copy_slice(&[(); 100]);
+    assert_eq!(lock.len(), 100);
+}
+
+/// `extend_from_slice` should panic without writing anything on length
+/// overflow
+#[test]
+#[should_panic(expected = "length overflow")]
+fn extend_from_slice_overflow() {
+    let lock = grow_lock!(3, [1]);
+    lock.write().unwrap().extend_from_slice(&[2, 3, 4]);
+}
+
+/// A panicking clone should drop the clones and leave the length untouched
+#[test]
+fn extend_from_slice_clone_panic() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    struct PanicOnClone<'a>(&'a AtomicUsize, bool);
+    impl Clone for PanicOnClone<'_> {
+        fn clone(&self) -> Self {
+            assert!(!self.1, "oops!");
+            Self(self.0, false)
+        }
+    }
+    impl Drop for PanicOnClone<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let counter = AtomicUsize::new(0);
+    let lock = GrowLock::with_capacity(5);
+    let values = [
+        PanicOnClone(&counter, false),
+        PanicOnClone(&counter, false),
+        PanicOnClone(&counter, true),
+    ];
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        lock.write().unwrap().extend_from_slice(&values);
+    }));
+
+    assert!(result.is_err());
+    assert!(lock.is_empty());
+    // the two clones were dropped.
+    assert_eq!(counter.load(Ordering::Relaxed), 2);
+}
+
+// ------------------- stage -------------------
+#[test]
+fn stage_commit() {
+    let lock = Arc::new(grow_lock!(5, [1]));
+    let mut guard = lock.write().unwrap();
+    let mut batch = guard.stage();
+    batch.push(2);
+    batch.extend([3, 4]);
+    assert_eq!(batch.as_slice(), &[2, 3, 4]);
+    assert_eq!(batch.remaining(), 1);
+    assert_eq!(lock.len(), 1);
+    batch.commit();
+    assert_eq!(&lock[..], &[1, 2, 3, 4]);
+
+    let mut batch = guard.stage();
+    assert!(batch.try_push(5).is_ok());
+    assert!(batch.try_push(6).is_err());
+    batch.commit();
+    drop(guard);
+    assert_eq!(&lock[..], &[1, 2, 3, 4, 5]);
+}
+
+/// Rolling back or dropping a batch drops its elements
+#[test]
+fn stage_rollback() {
+    let counter = AtomicUsize::new(0);
+    let lock = GrowLock::with_capacity(5);
+    let mut guard = lock.write().unwrap();
+
+    let mut batch = guard.stage();
+    batch.push(AddOnDrop(&counter));
+    batch.push(AddOnDrop(&counter));
+    batch.rollback();
+    assert_eq!(counter.load(Ordering::Relaxed), 2);
+
+    let mut batch = guard.stage();
+    batch.push(AddOnDrop(&counter));
+    drop(batch);
+    assert_eq!(counter.load(Ordering::Relaxed), 3);
+
+    // the slots of a dropped batch are reused
+    guard.stage().commit();
+    guard.push(AddOnDrop(&counter));
+    assert_eq!(guard.len(), 1);
+    drop(guard);
+    drop(lock);
+    assert_eq!(counter.load(Ordering::Relaxed), 4);
+}
+
+/// A panic in the middle of a batch leaves the readers seeing the old
+/// length
+#[test]
+fn stage_panic() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let counter = AtomicUsize::new(0);
+    let lock = grow_lock!(5, [AddOnDrop(&counter)]);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = lock.write().unwrap();
+        let mut batch = guard.stage();
+        for _ in 0..10 {
+            batch.push(AddOnDrop(&counter));
+        }
+        batch.commit();
+    }));
+
+    assert!(result.is_err());
+    assert_eq!(lock.len(), 1);
+    // 4 staged elements, plus the one which didn't fit.
+    assert_eq!(counter.load(Ordering::Relaxed), 5);
+}
+
+// ------------------- spare capacity -------------------
+#[test]
+fn spare_capacity() {
+    let lock = grow_lock!(5, [1]);
+    let mut guard = lock.write().unwrap();
+    let spare = guard.spare_capacity_mut();
+    assert_eq!(spare.len(), 4);
+    spare[0].write(2);
+    spare[1].write(3);
+    // SAFETY: the first 2 elements of the spare capacity are initialized.
+    unsafe { guard.publish(2) };
+    assert_eq!(&lock[..], &[1, 2, 3]);
+
+    guard.spare_capacity_mut()[1].write(5);
+    guard.spare_capacity_mut()[0].write(4);
+    // SAFETY: the elements up to 5 are initialized.
+    unsafe { guard.set_len(5) };
+    assert!(guard.spare_capacity_mut().is_empty());
+    drop(guard);
+    assert_eq!(&lock[..], &[1, 2, 3, 4, 5]);
+}
+
+/// The published elements are dropped with the lock
+#[test]
+fn spare_capacity_drop() {
+    let counter = AtomicUsize::new(0);
+    let lock = GrowLock::with_capacity(3);
+    let mut guard = lock.write().unwrap();
+    for slot in guard.spare_capacity_mut() {
+        slot.write(AddOnDrop(&counter));
+    }
+    // SAFETY: the spare capacity is initialized.
+    unsafe { guard.publish(3) };
+    drop(guard);
+    drop(lock);
+    assert_eq!(counter.load(Ordering::Relaxed), 3);
+}
+
+/// Published elements can't be removed with `set_len`
+#[test]
+#[should_panic(expected = "cannot shrink the length from 2 to 1")]
+fn set_len_shrink() {
+    let lock = grow_lock!(3, [1, 2]);
+    // SAFETY: no element is published.
+    unsafe { lock.write().unwrap().set_len(1) };
+}
+
+#[test]
+#[should_panic(expected = "length overflow")]
+fn publish_overflow() {
+    let lock = grow_lock!(3, [1, 2]);
+    // SAFETY: this panics before publishing anything.
+    unsafe { lock.write().unwrap().publish(2) };
+}
+
 // ------------------- test drop -------------------
 
 /// Tests if elements are correctly dropped when the [`GrowLock`] is
@@ -268,6 +599,566 @@ fn write_contention() {
     assert_eq!(lock.len(), CAP);
 }
 
+/// An owned guard can be stored in a struct, and keeps the lock alive
+#[test]
+fn write_arc() {
+    struct Writer {
+        guard: OwnedGrowGuard<i32>,
+    }
+
+    let lock = Arc::new(GrowLock::with_capacity(3));
+    let mut writer = Writer {
+        guard: lock.write_arc().unwrap(),
+    };
+    assert!(lock.try_write().is_err());
+    assert!(matches!(
+        lock.try_write_arc(),
+        Err(TryLockError::WouldBlock)
+    ));
+    drop(lock);
+
+    writer.guard.extend([1, 2]);
+    writer.guard.push(3);
+    assert!(writer.guard.try_push(4).is_err());
+    let lock = Arc::clone(OwnedGrowGuard::lock(&writer.guard));
+    drop(writer);
+    assert_eq!(&lock[..], &[1, 2, 3]);
+    assert!(lock.try_write_arc().is_ok());
+}
+
+/// Dropping an owned guard drops the lock if it was the last `Arc`
+#[test]
+fn write_arc_drop() {
+    let counter = AtomicUsize::new(0);
+    let lock = Arc::new(GrowLock::with_capacity(3));
+    let mut guard = lock.write_arc().unwrap();
+    drop(lock);
+    guard.push(AddOnDrop(&counter));
+    guard.push(AddOnDrop(&counter));
+    drop(guard);
+    assert_eq!(counter.load(Ordering::Relaxed), 2);
+}
+
+/// An owned guard can be moved into another thread, and released there
+#[test]
+fn write_arc_send() {
+    let lock = Arc::new(GrowLock::with_capacity(3));
+    let mut guard = lock.write_arc().unwrap();
+    guard.push(1);
+    thread::spawn(move || {
+        guard.push(2);
+        guard.push(3);
+    })
+    .join()
+    .unwrap();
+    assert_eq!(&lock[..], &[1, 2, 3]);
+    assert!(lock.try_write().is_ok());
+}
+
+#[test]
+fn try_write_for() {
+    let lock = Arc::new(GrowLock::with_capacity(3));
+    let guard = lock.write().unwrap();
+    let timeout = Duration::from_millis(10);
+    assert!(matches!(
+        lock.try_write_for(timeout),
+        Err(TryLockError::WouldBlock)
+    ));
+
+    // the guard is released while waiting.
+    let handle = thread::spawn({
+        let lock = Arc::clone(&lock);
+        move || {
+            let mut guard = lock.try_write_for(Duration::MAX).unwrap();
+            guard.push(2);
+        }
+    });
+    thread::sleep(timeout);
+    drop(guard);
+    handle.join().unwrap();
+    assert_eq!(&lock[..], &[2]);
+}
+
+#[test]
+fn try_write_until() {
+    let lock = GrowLock::with_capacity(3);
+    // a free lock is taken even if the deadline has passed.
+    let past = Instant::now();
+    let mut guard = lock.try_write_until(past).unwrap();
+    guard.push(1);
+    let deadline = Instant::now() + Duration::from_millis(10);
+    assert!(matches!(
+        lock.try_write_until(deadline),
+        Err(TryLockError::WouldBlock)
+    ));
+    assert!(Instant::now() >= deadline);
+}
+
+// ------------------- push_concurrent -------------------
+
+/// Tests that every producer gets its own slot, and that `len` reaches the
+/// number of pushed elements
+#[test]
+fn push_concurrent_contention() {
+    const THREADS: usize = 8;
+    const CAP: usize = 8000;
+
+    let lock = Arc::new(GrowLock::with_capacity(CAP));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || {
+                for i in 0..(CAP / THREADS) {
+                    let value = t * (CAP / THREADS) + i;
+                    let index = lock.push_concurrent(value).unwrap();
+                    assert!(index < CAP);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(lock.is_full());
+    let mut elems = lock.to_vec();
+    elems.sort_unstable();
+    assert!(elems.into_iter().eq(0..CAP));
+    assert_eq!(lock.push_concurrent(CAP), Err(CAP));
+}
+
+/// Tests that lock-free producers and guards can write at the same time
+#[test]
+fn push_concurrent_with_guard() {
+    const THREADS: usize = 4;
+    const PUSHES: usize = 500;
+
+    let lock = Arc::new(GrowLock::with_capacity(THREADS * PUSHES * 2));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || {
+                for i in 0..PUSHES {
+                    if t % 2 == 0 {
+                        let mut guard = lock.write().unwrap();
+                        guard.extend([i, i]);
+                    } else {
+                        lock.push_concurrent(i).unwrap();
+                        lock.push_concurrent(i).unwrap();
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert!(lock.is_full());
+    assert!(lock.try_write().unwrap().try_push(0).is_err());
+    let sum: usize = lock.iter().sum();
+    assert_eq!(sum, THREADS * PUSHES * (PUSHES - 1));
+}
+
+/// Tests that readers only see initialized elements while producers push
+#[test]
+fn push_concurrent_read() {
+    const THREADS: usize = 4;
+    const CAP: usize = 4000;
+
+    let lock = Arc::new(GrowLock::with_capacity(CAP));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || {
+                for i in 0..(CAP / THREADS) {
+                    lock.push_concurrent(i.to_string()).unwrap();
+                }
+            })
+        })
+        .collect();
+    while !lock.is_full() {
+        for elem in lock.iter() {
+            assert!(elem.parse::<usize>().unwrap() < CAP / THREADS);
+        }
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+/// Tests that elements pushed concurrently are dropped, ZSTs included
+#[test]
+fn push_concurrent_drop() {
+    static ZST_COUNTER: AtomicUsize = AtomicUsize::new(0);
+    struct AddZST;
+    impl Drop for AddZST {
+        fn drop(&mut self) {
+            ZST_COUNTER.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let counter = AtomicUsize::new(0);
+    {
+        let lock = GrowLock::with_capacity(10);
+        for _ in 0..10 {
+            assert!(lock.push_concurrent(AddOnDrop(&counter)).is_ok());
+        }
+        // the rejected element is given back, and dropped here
+        assert!(lock.push_concurrent(AddOnDrop(&counter)).is_err());
+    }
+    assert_eq!(counter.load(Ordering::Relaxed), 11);
+
+    {
+        let lock = GrowLock::with_capacity(0);
+        for i in 0..10 {
+            assert_eq!(lock.push_concurrent(AddZST).ok(), Some(i));
+        }
+        assert_eq!(lock.len(), 10);
+    }
+    assert_eq!(ZST_COUNTER.load(Ordering::Relaxed), 10);
+}
+
+/// Tests that taking apart a lock after a `push_concurrent` frees its
+/// bookkeeping
+#[test]
+fn push_concurrent_no_leak() {
+    fn lock() -> GrowLock<u8> {
+        let lock = GrowLock::with_capacity(1 << 16);
+        lock.push_concurrent(1).unwrap();
+        lock
+    }
+
+    assert_eq!(leaked(|| drop(lock())), 0);
+    assert_eq!(leaked(|| drop(lock().freeze())), 0);
+    assert_eq!(leaked(|| drop(lock().into_iter())), 0);
+    assert_eq!(leaked(|| drop(lock().into_boxed_slice())), 0);
+    assert_eq!(leaked(|| drop(lock().with_lock::<StdLock>())), 0);
+    assert_eq!(leaked(|| drop(Vec::from(lock()))), 0);
+    assert_eq!(leaked(|| drop(Box::<[u8]>::from(lock()))), 0);
+    assert_eq!(leaked(|| drop(Arc::<[u8]>::from(lock()))), 0);
+    assert_eq!(
+        leaked(|| {
+            let (ptr, len, cap) = lock().into_parts();
+            // SAFETY: the parts come from `into_parts`.
+            drop(unsafe { GrowLock::<u8>::from_parts(ptr, len, cap) });
+        }),
+        0
+    );
+}
+
+// ------------------- wait -------------------
+
+/// Tests that waiters are woken up by a slow writer
+#[test]
+fn wait_for_len() {
+    let lock = Arc::new(GrowLock::with_capacity(10));
+    let waiters: Vec<_> = (1..=3)
+        .map(|n| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || {
+                assert!(lock.wait_for_len(n) >= n);
+                assert_eq!(&lock[..n], &[1, 2, 3][..n]);
+            })
+        })
+        .collect();
+
+    let mut guard = lock.write().unwrap();
+    for i in 1..=3 {
+        thread::sleep(Duration::from_millis(20));
+        guard.push(i);
+    }
+    drop(guard);
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+}
+
+/// Tests that waiters are woken up by `extend` and by lock-free
+/// producers
+#[test]
+fn wait_for_len_extend_concurrent() {
+    let lock = Arc::new(GrowLock::with_capacity(10));
+    let waiter = thread::spawn({
+        let lock = Arc::clone(&lock);
+        move || lock.wait_for_len(5)
+    });
+    thread::sleep(Duration::from_millis(20));
+    lock.write().unwrap().extend([1, 2, 3, 4]);
+    lock.push_concurrent(5).unwrap();
+    assert_eq!(waiter.join().unwrap(), 5);
+}
+
+/// Tests that the timeout elapses if no one pushes
+#[test]
+fn wait_for_len_timeout() {
+    let lock = grow_lock!(3, [1]);
+    let timeout = Duration::from_millis(30);
+    assert_eq!(lock.wait_for_len_timeout(1, timeout), Some(1));
+    assert_eq!(lock.wait_for_len_timeout(2, timeout), None);
+
+    // a writer wakes the waiter up before the timeout
+    let lock = Arc::new(lock);
+    let waiter = thread::spawn({
+        let lock = Arc::clone(&lock);
+        move || lock.wait_for_len_timeout(2, Duration::from_mins(1))
+    });
+    thread::sleep(Duration::from_millis(20));
+    lock.write().unwrap().push(2);
+    assert_eq!(waiter.join().unwrap(), Some(2));
+}
+
+/// Waiting for an unreachable length should panic
+#[test]
+#[should_panic(expected = "the capacity is 3")]
+fn wait_for_len_overflow() {
+    let lock: GrowLock<i32> = grow_lock!(3);
+    lock.wait_for_len(4);
+}
+
+// ------------------- async -------------------
+#[cfg(feature = "async")]
+mod asynchronous {
+    use {
+        super::*,
+        futures_core::{FusedStream, Stream},
+        std::{
+            pin::pin,
+            task::{Context, Poll, Wake, Waker},
+            thread::Thread,
+        },
+    };
+
+    /// Waker unparking the thread running [`block_on`].
+    struct Unpark(Thread);
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Minimal executor, polling `future` on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn wait_len() {
+        let lock = Arc::new(GrowLock::with_capacity(5));
+        assert_eq!(block_on(lock.wait_len(0)), 0);
+
+        let writer = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                for i in 0..5 {
+                    thread::sleep(Duration::from_millis(5));
+                    lock.write().unwrap().push(i);
+                }
+            }
+        });
+        assert!(block_on(lock.wait_len(3)) >= 3);
+        assert_eq!(&lock[..3], &[0, 1, 2]);
+        assert_eq!(block_on(lock.wait_len(5)), 5);
+        writer.join().unwrap();
+    }
+
+    /// Polling a pending future twice should not register its waker twice
+    #[test]
+    fn wait_len_repoll() {
+        let lock = grow_lock!(2, [1]);
+        let waker = Waker::from(Arc::new(Unpark(thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = pin!(lock.wait_len(2));
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        lock.write().unwrap().push(2);
+        assert_eq!(future.as_mut().poll(&mut cx), Poll::Ready(2));
+    }
+
+    /// Tests that the wakers still registered are dropped when the lock
+    /// is taken apart
+    #[test]
+    fn wait_len_freeze() {
+        let lock = grow_lock!(2, [1]);
+        let unpark = Arc::new(Unpark(thread::current()));
+        let waker = Waker::from(Arc::clone(&unpark));
+        let mut cx = Context::from_waker(&waker);
+        {
+            let mut future = pin!(lock.wait_len(2));
+            assert_eq!(future.as_mut().poll(&mut cx), Poll::Pending);
+        }
+        drop(waker);
+        assert_eq!(Arc::strong_count(&unpark), 2);
+        drop(lock.freeze());
+        assert_eq!(Arc::strong_count(&unpark), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the capacity is 3")]
+    fn wait_len_overflow() {
+        let lock: GrowLock<i32> = grow_lock!(3);
+        drop(lock.wait_len(4));
+    }
+
+    #[test]
+    fn subscribe() {
+        let lock = Arc::new(grow_lock!(6, [0, 1]));
+        let writer = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                for i in 2..6 {
+                    thread::sleep(Duration::from_millis(5));
+                    lock.push_concurrent(i).unwrap();
+                }
+            }
+        });
+
+        let mut subscriber = lock.subscribe_from(0);
+        let mut items = vec![];
+        while let Some(&item) = block_on(subscriber.next()) {
+            items.push(item);
+        }
+        assert_eq!(items, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(subscriber.position(), 6);
+        assert!(subscriber.is_terminated());
+        writer.join().unwrap();
+    }
+
+    /// `subscribe` should only yield the elements published after it
+    #[test]
+    fn subscribe_new_elements() {
+        let lock = grow_lock!(4, [1, 2]);
+        let mut subscriber = lock.subscribe();
+        assert_eq!(subscriber.size_hint(), (0, Some(2)));
+
+        lock.write().unwrap().extend([3, 4]);
+        assert_eq!(subscriber.size_hint(), (2, Some(2)));
+        assert_eq!(block_on(subscriber.next()), Some(&3));
+        assert_eq!(block_on(subscriber.next()), Some(&4));
+        assert_eq!(block_on(subscriber.next()), None);
+    }
+
+    /// Waker counting how many times it is woken up.
+    #[derive(Default)]
+    struct Count(AtomicUsize);
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn write_async() {
+        let lock = Arc::new(GrowLock::with_capacity(100));
+        let writers: Vec<_> = (0..4)
+            .map(|i| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    for j in 0..25 {
+                        block_on(lock.write_async())
+                            .unwrap()
+                            .push(i * 25 + j);
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let mut values = lock.to_vec();
+        values.sort_unstable();
+        assert_eq!(values, (0..100).collect::<Vec<_>>());
+    }
+
+    /// Asynchronous writers take the lock in the order they arrived
+    #[test]
+    fn write_async_fifo() {
+        let lock: GrowLock<i32> = GrowLock::with_capacity(3);
+        let count = Arc::new(Count::default());
+        let waker = Waker::from(Arc::clone(&count));
+        let mut cx = Context::from_waker(&waker);
+
+        let guard = lock.write().unwrap();
+        let mut first = pin!(lock.write_async());
+        let mut second = pin!(lock.write_async());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        drop(guard);
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+
+        // `second` can't overtake `first`, even with the lock released
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        let Poll::Ready(Ok(mut guard)) = first.as_mut().poll(&mut cx)
+        else {
+            panic!("the first writer should take the lock");
+        };
+        guard.push(1);
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        drop(guard);
+        assert_eq!(count.0.load(Ordering::Relaxed), 2);
+
+        let Poll::Ready(Ok(mut guard)) = second.as_mut().poll(&mut cx)
+        else {
+            panic!("the second writer should take the lock");
+        };
+        guard.push(2);
+        drop(guard);
+        assert_eq!(&lock[..], &[1, 2]);
+    }
+
+    /// Dropping the front of the queue hands the lock to the next writer
+    #[test]
+    fn write_async_cancel() {
+        let lock: GrowLock<i32> = GrowLock::with_capacity(3);
+        let count = Arc::new(Count::default());
+        let waker = Waker::from(Arc::clone(&count));
+        let mut cx = Context::from_waker(&waker);
+
+        let guard = lock.write().unwrap();
+        let mut first = Box::pin(lock.write_async());
+        let mut second = pin!(lock.write_async());
+        assert!(first.as_mut().poll(&mut cx).is_pending());
+        assert!(second.as_mut().poll(&mut cx).is_pending());
+        drop(guard);
+        drop(first);
+        assert_eq!(count.0.load(Ordering::Relaxed), 2);
+        assert!(matches!(
+            second.as_mut().poll(&mut cx),
+            Poll::Ready(Ok(_))
+        ));
+    }
+
+    #[test]
+    fn write_async_poisoning() {
+        let lock = Arc::new(grow_lock!(3, [1]));
+        let _ = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let _guard = lock.write().unwrap();
+                panic!("poisoning the lock");
+            }
+        })
+        .join();
+
+        let Err(poisoned) = block_on(lock.write_async()) else {
+            panic!("the lock should be poisoned");
+        };
+        let mut guard = poisoned.into_inner();
+        guard.push(2);
+        drop(guard);
+        assert_eq!(&lock[..], &[1, 2]);
+    }
+}
+
 // ------------------- read -------------------
 
 /// tests that we can still read while writing
@@ -338,6 +1229,168 @@ fn poisoning() {
     .join();
 
     assert!(lock.write().is_err());
+    assert!(matches!(
+        lock.try_write_for(Duration::ZERO),
+        Err(TryLockError::Poisoned(_))
+    ));
+}
+
+/// Tests if the owned guards get correctly poisoned on panics.
+#[test]
+fn poisoning_arc() {
+    let lock = Arc::new(GrowLock::with_capacity(5));
+    let _ = thread::spawn({
+        let lock_clone = Arc::clone(&lock);
+        move || {
+            let mut guard = lock_clone.write_arc().unwrap();
+            guard.push('a');
+            panic!("oops!");
+        }
+    })
+    .join();
+
+    assert!(lock.write_arc().is_err());
+    assert!(matches!(
+        lock.try_write_arc(),
+        Err(TryLockError::Poisoned(_))
+    ));
+}
+
+/// Tests that changing the write lock keeps the poisoning.
+#[test]
+fn poisoning_with_lock() {
+    let lock = GrowLock::with_capacity(5);
+    let _ = thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.write().unwrap();
+            guard.push('a');
+            panic!("oops!");
+        })
+        .join()
+    });
+
+    let lock = lock.with_lock::<StdLock>();
+    assert!(lock.write().is_err());
+    assert_eq!(&lock[..], &['a']);
+}
+
+/// Tests that clearing the poison lets the writers in again.
+#[test]
+fn clear_poison() {
+    let lock = GrowLock::with_capacity(5);
+    assert!(!lock.is_poisoned());
+    let _ = thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.write().unwrap();
+            guard.push('a');
+            panic!("oops!");
+        })
+        .join()
+    });
+
+    assert!(lock.is_poisoned());
+    // the clone is not poisoned.
+    assert!(!lock.clone().is_poisoned());
+    lock.clear_poison();
+    assert!(!lock.is_poisoned());
+    lock.write().unwrap().push('b');
+    assert_eq!(&lock[..], &['a', 'b']);
+}
+
+/// Tests that a non-poisoning [`GrowLock`] stays usable after a panic.
+#[test]
+fn without_poisoning() {
+    let lock = GrowLock::with_capacity(5);
+    let _ = thread::scope(|s| {
+        s.spawn(|| {
+            let _guard = lock.write().unwrap();
+            panic!("oops!");
+        })
+        .join()
+    });
+    // disabling the poisoning clears it.
+    let lock = lock.without_poisoning().with_lock::<StdLock>();
+    assert!(!lock.is_poisoned());
+
+    let _ = thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = lock.write().unwrap();
+            guard.push('a');
+            panic!("oops!");
+        })
+        .join()
+    });
+    assert!(!lock.is_poisoned());
+    assert!(lock.try_write().is_ok());
+    // the clone keeps the mode.
+    let clone = lock.clone();
+    let _ = thread::scope(|s| {
+        s.spawn(|| {
+            let _guard = clone.write().unwrap();
+            panic!("oops!");
+        })
+        .join()
+    });
+    assert!(!clone.is_poisoned());
+}
+
+// ------------------- lock backends -------------------
+
+/// Pushes from several writers on the given write lock backend.
+fn lock_backend<L: WriteLock + 'static>() {
+    const THREADS: usize = 8;
+    const PUSHES: usize = 100;
+    let lock = Arc::new(
+        GrowLock::with_capacity(THREADS * PUSHES).with_lock::<L>(),
+    );
+    let handles: Vec<_> = (0..THREADS)
+        .map(|i| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || {
+                for j in 0..PUSHES {
+                    lock.write().unwrap().push(i * PUSHES + j);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    let mut values = lock.to_vec();
+    values.sort_unstable();
+    assert!(values.into_iter().eq(0..THREADS * PUSHES));
+
+    let guard = lock.write().unwrap();
+    assert!(matches!(lock.try_write(), Err(TryLockError::WouldBlock)));
+    assert!(matches!(
+        lock.try_write_for(Duration::from_millis(10)),
+        Err(TryLockError::WouldBlock)
+    ));
+    drop(guard);
+    assert!(lock.try_write_for(Duration::from_millis(10)).is_ok());
+}
+
+#[test]
+fn lock_std() {
+    lock_backend::<StdLock>();
+}
+
+#[cfg(feature = "spin")]
+#[test]
+fn lock_spin() {
+    lock_backend::<crate::lock::SpinLock>();
+}
+
+#[cfg(feature = "ticket")]
+#[test]
+fn lock_ticket() {
+    lock_backend::<crate::lock::TicketLock>();
+}
+
+#[cfg(all(feature = "futex", target_os = "linux"))]
+#[test]
+fn lock_futex() {
+    lock_backend::<crate::lock::FutexLock>();
 }
 
 // ------------------- traits -------------------
@@ -400,6 +1453,220 @@ fn ptr_eq() {
     assert!(lock4.ptr_eq(lock3));
 }
 
+#[test]
+fn clone() {
+    let lock = grow_lock!(5, [String::from("a"), String::from("b")]);
+    let clone = lock.clone();
+    assert_eq!(clone, lock);
+    assert_eq!(clone.capacity(), 5);
+    assert!(clone.ptr_ne(&lock));
+
+    let lock = grow_lock!(5, [(), ()]);
+    assert_eq!(lock.clone().len(), 2);
+}
+
+#[test]
+fn from_iter() {
+    let lock: GrowLock<_> = (0..4).collect();
+    assert_eq!(lock, [0, 1, 2, 3]);
+    assert_eq!(lock.capacity(), 4);
+
+    let lock: GrowLock<_> = (0..10).filter(|n| n % 3 == 0).collect();
+    assert_eq!(lock, [0, 3, 6, 9]);
+    assert!(lock.capacity() >= 4);
+
+    // the upper bound of the size hint is not trusted.
+    let lock: GrowLock<_> = iter::repeat_n(1u64, usize::MAX / 8)
+        .take_while(|_| false)
+        .collect();
+    assert!(lock.is_empty());
+
+    // without an upper bound, all the elements are collected anyway.
+    let lock: GrowLock<_> = (0..).take_while(|&n| n < 20).collect();
+    assert_eq!(lock.len(), 20);
+}
+
+#[test]
+fn extend() {
+    let mut lock = grow_lock!(5, [1]);
+    lock.extend([2, 3]);
+    lock.extend(&[4, 5]);
+    assert_eq!(lock, [1, 2, 3, 4, 5]);
+}
+
+#[test]
+#[should_panic = "length overflow"]
+fn extend_overflow() {
+    let mut lock = grow_lock!(2, [1]);
+    lock.extend([2, 3]);
+}
+
+#[test]
+fn into_iter() {
+    let lock = grow_lock!(5, [1, 2, 3, 4]);
+    let mut iter = lock.into_iter();
+    assert_eq!(iter.len(), 4);
+    assert_eq!(iter.next(), Some(1));
+    assert_eq!(iter.next_back(), Some(4));
+    assert_eq!(iter.as_slice(), &[2, 3]);
+    assert_eq!(iter.collect::<Vec<_>>(), [2, 3]);
+
+    let lock = grow_lock!(5, [(), (), ()]);
+    assert_eq!(lock.into_iter().rev().count(), 3);
+
+    let lock = grow_lock!(5, [1, 2]);
+    assert_eq!((&lock).into_iter().sum::<i32>(), 3);
+}
+
+/// The elements which are not yielded are dropped with the iterator
+#[test]
+fn into_iter_drop() {
+    let counter = AtomicUsize::new(0);
+    let lock = grow_lock!(
+        5,
+        [
+            AddOnDrop(&counter),
+            AddOnDrop(&counter),
+            AddOnDrop(&counter)
+        ]
+    );
+    let mut iter = lock.into_iter();
+    drop(iter.next());
+    assert_eq!(counter.load(Ordering::Relaxed), 1);
+    drop(iter);
+    assert_eq!(counter.load(Ordering::Relaxed), 3);
+}
+
+// ------------------- exclusive access -------------------
+
+#[test]
+fn truncate() {
+    let counter = AtomicUsize::new(0);
+    let mut lock = GrowLock::with_capacity(5);
+    lock.extend([
+        AddOnDrop(&counter),
+        AddOnDrop(&counter),
+        AddOnDrop(&counter),
+    ]);
+    lock.truncate(5);
+    assert_eq!(counter.load(Ordering::Relaxed), 0);
+    lock.truncate(1);
+    assert_eq!(counter.load(Ordering::Relaxed), 2);
+    lock.clear();
+    assert_eq!(counter.load(Ordering::Relaxed), 3);
+    assert!(lock.is_empty());
+    assert_eq!(lock.capacity(), 5);
+}
+
+/// The lock-free producers start again from the new length
+#[test]
+fn truncate_push_concurrent() {
+    let mut lock = GrowLock::with_capacity(4);
+    for n in 0..4 {
+        lock.push_concurrent(n).unwrap();
+    }
+    lock.truncate(1);
+    assert_eq!(lock.push_concurrent(10), Ok(1));
+    assert_eq!(lock, [0, 10]);
+
+    lock.retain(|&n| n != 0);
+    assert_eq!(lock.push_concurrent(20), Ok(1));
+    assert_eq!(lock, [10, 20]);
+}
+
+#[test]
+fn retain() {
+    let mut lock = grow_lock!(10, [String::from("a"), String::new()]);
+    lock.write().unwrap().push(String::from("b"));
+    lock.retain(|s| !s.is_empty());
+    assert_eq!(lock, ["a", "b"]);
+    assert_eq!(lock.capacity(), 10);
+
+    let mut lock = grow_lock!(10, [(), (), ()]);
+    let mut keep = false;
+    lock.retain(|()| {
+        keep = !keep;
+        keep
+    });
+    assert_eq!(lock.len(), 2);
+}
+
+/// A panicking predicate keeps the unprocessed elements
+#[test]
+fn retain_panic() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let counter = AtomicUsize::new(0);
+    let mut lock = GrowLock::with_capacity(5);
+    lock.extend((0..5).map(|n| (n, AddOnDrop(&counter))));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        lock.retain(|&(n, _)| {
+            assert!(n != 3, "oops!");
+            n % 2 == 0
+        });
+    }));
+    assert!(result.is_err());
+    assert_eq!(counter.load(Ordering::Relaxed), 1);
+    assert!(lock.iter().map(|&(n, _)| n).eq([0, 2, 3, 4]));
+    drop(lock);
+    assert_eq!(counter.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+fn reserve() {
+    let mut lock = grow_lock!(2, [String::from("a"), String::from("b")]);
+    lock.reserve(0);
+    assert_eq!(lock.capacity(), 2);
+    lock.reserve(3);
+    assert_eq!(lock.capacity(), 5);
+    lock.write().unwrap().push(String::from("c"));
+    assert_eq!(lock, ["a", "b", "c"]);
+
+    let mut lock = GrowLock::<u8, _>::with_capacity_in(0, System);
+    assert_eq!(lock.try_reserve(4), Ok(()));
+    assert_eq!(lock.capacity(), 4);
+    assert_eq!(lock.push_concurrent(1), Ok(0));
+    assert_eq!(
+        lock.try_reserve(usize::MAX),
+        Err(TryReserveError::CapacityOverflow)
+    );
+    assert_eq!(
+        lock.try_reserve(isize::MAX as usize),
+        Err(TryReserveError::CapacityOverflow)
+    );
+    assert_eq!(lock, [1]);
+
+    let mut lock = GrowLock::<()>::with_capacity(0);
+    lock.reserve(10);
+    assert_eq!(lock.capacity(), usize::MAX);
+}
+
+#[test]
+fn shrink_to_fit() {
+    let mut lock = grow_lock!(10, [1, 2, 3]);
+    lock.shrink_to_fit();
+    assert_eq!(lock.capacity(), 3);
+    assert_eq!(lock, [1, 2, 3]);
+    assert_eq!(lock.push_concurrent(4), Err(4));
+
+    lock.clear();
+    lock.shrink_to_fit();
+    assert_eq!(lock.capacity(), 0);
+    lock.reserve(1);
+    lock.write().unwrap().push(5);
+    assert_eq!(lock, [5]);
+}
+
+#[test]
+fn get_mut() {
+    let mut lock = grow_lock!(5, [1, 2, 3]);
+    *lock.get_mut(0).unwrap() = 10;
+    lock.get_mut(1..).unwrap().fill(0);
+    assert_eq!(lock.get_mut(3), None);
+    lock.as_mut_slice().reverse();
+    assert_eq!(lock, [0, 0, 10]);
+}
+
 // ------------------- getters -------------------
 
 #[test]
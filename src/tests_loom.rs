@@ -2,14 +2,42 @@
 //! `RUSTFLAGS="--cfg loom" cargo test tests_loom --release`
 
 use {
-    crate::grow_lock,
-    loom::{sync::Arc, thread},
+    crate::{GrowLock, grow_lock, interner::GrowInterner},
+    loom::{cell::UnsafeCell, sync::Arc, thread},
+    std::hash::{Hash, Hasher},
 };
 
 /// The max loom's thread pool is 4. To keep the tests fast, we use
 /// a maximum of 3.
 const THREADS: usize = 3;
 
+/// Helper struct whose initialization is tracked by loom: reading it
+/// from a thread that is not ordered after its creation is reported as a
+/// causality violation.
+struct Tracked(UnsafeCell<usize>);
+impl Tracked {
+    fn new(value: usize) -> Self {
+        Self(UnsafeCell::new(value))
+    }
+    fn get(&self) -> usize {
+        // SAFETY: `Tracked` is never mutated after its creation.
+        self.0.with(|ptr| unsafe { *ptr })
+    }
+}
+// SAFETY: `Tracked` is never mutated after its creation.
+unsafe impl Sync for Tracked {}
+impl PartialEq for Tracked {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+impl Eq for Tracked {}
+impl Hash for Tracked {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get().hash(state);
+    }
+}
+
 /// Tests that when a reader sees the length increment, the last element is
 /// correctly initialized
 #[test]
@@ -29,9 +57,187 @@ fn length_visibility() {
     });
 }
 
+/// Tests that an element is initialized **before** its publication,
+/// i.e. the `len` store of the writer is `Release` and the `len` load of
+/// the reader is `Acquire`.
 #[test]
-fn write_contention() {
+fn publication_push() {
+    loom::model(|| {
+        let lock = Arc::new(grow_lock!(2));
+        let writer = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let mut guard = lock.write().unwrap();
+                guard.push(Tracked::new(1));
+                guard.push(Tracked::new(2));
+            }
+        });
+        let reader = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                for (i, elem) in lock.iter().enumerate() {
+                    assert_eq!(elem.get(), i + 1);
+                }
+            }
+        });
+
+        writer.join().unwrap();
+        reader.join().unwrap();
+    });
+}
+
+/// Tests that a committed batch is seen either entirely or not at all, and
+/// that its elements are initialized
+#[test]
+fn publication_stage() {
+    loom::model(|| {
+        let lock = Arc::new(grow_lock!(2));
+        let writer = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let mut guard = lock.write().unwrap();
+                let mut batch = guard.stage();
+                batch.push(Tracked::new(1));
+                batch.push(Tracked::new(2));
+                batch.commit();
+            }
+        });
+        let reader = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let slice = lock.as_slice();
+                assert!(slice.len() == 0 || slice.len() == 2);
+                for (i, elem) in slice.iter().enumerate() {
+                    assert_eq!(elem.get(), i + 1);
+                }
+            }
+        });
+
+        writer.join().unwrap();
+        reader.join().unwrap();
+    });
+}
+
+/// Same as [`publication_push`], but with `try_push`, and reading through
+/// [`GrowLock::get`](crate::GrowLock).
+#[test]
+fn publication_try_push() {
+    loom::model(|| {
+        let lock = Arc::new(grow_lock!(1));
+        let writer = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let mut guard = lock.write().unwrap();
+                guard.try_push(Tracked::new(42)).unwrap();
+            }
+        });
+        let reader = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || lock.get(0).map(Tracked::get)
+        });
+
+        writer.join().unwrap();
+        assert!(matches!(reader.join().unwrap(), None | Some(42)));
+    });
+}
+
+/// Tests that two readers on different threads both see initialized
+/// elements, whatever length they observe.
+#[test]
+fn publication_many_readers() {
+    loom::model(|| {
+        let lock = Arc::new(grow_lock!(1));
+        thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                let mut guard = lock.write().unwrap();
+                guard.push(Tracked::new(7));
+            }
+        });
+        let reader = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || lock.first().map(Tracked::get)
+        });
+
+        let first = lock.first().map(Tracked::get);
+        assert!(matches!(first, None | Some(7)));
+        assert!(matches!(reader.join().unwrap(), None | Some(7)));
+    });
+}
+
+/// Tests that lock-free producers publish their elements only after
+/// writing them, and that `len` covers all of them at the end.
+#[test]
+fn push_concurrent_publication() {
+    loom::model(|| {
+        let lock = Arc::new(grow_lock!(2));
+        let handles: Vec<_> = (1..=2)
+            .map(|i| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    lock.push_concurrent(Tracked::new(i)).ok().unwrap();
+                })
+            })
+            .collect();
+
+        for elem in lock.iter() {
+            assert!(matches!(elem.get(), 1 | 2));
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(lock.len(), 2);
+    });
+}
+
+/// Tests that a guard and a lock-free producer never write the same slot.
+#[test]
+fn push_concurrent_with_guard() {
     loom::model(|| {
+        let lock = Arc::new(grow_lock!(2));
+        let producer = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || lock.push_concurrent(Tracked::new(1)).ok().unwrap()
+        });
+        {
+            let mut guard = lock.write().unwrap();
+            guard.push(Tracked::new(2));
+        }
+
+        let index = producer.join().unwrap();
+        assert_eq!(lock.len(), 2);
+        assert_eq!(lock[index].get(), 1);
+        assert_eq!(lock[1 - index].get(), 2);
+    });
+}
+
+/// Tests that a waiter is never left sleeping after the writer publishes
+/// the length it waits for (loom reports it as a deadlock).
+#[test]
+fn wait_for_len() {
+    loom::model(|| {
+        let lock = Arc::new(GrowLock::<Tracked>::with_capacity(1));
+        let waiter = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                assert_eq!(lock.wait_for_len(1), 1);
+                lock[0].get()
+            }
+        });
+        lock.write().unwrap().push(Tracked::new(5));
+
+        assert_eq!(waiter.join().unwrap(), 5);
+    });
+}
+
+/// Tests that the writers sleeping on the write lock are always woken up
+#[test]
+fn write_contention() {
+    // the write lock sleeps on a mutex and a condvar: without a bound,
+    // the model doesn't finish in a reasonable time.
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound = Some(3);
+    builder.check(|| {
         let lock = Arc::new(grow_lock!(THREADS));
         let mut handles = Vec::with_capacity(THREADS);
         for i in 0..THREADS {
@@ -51,6 +257,56 @@ fn write_contention() {
     });
 }
 
+/// Tests the mutual exclusion of a write lock backend, without a
+/// [`GrowLock`] (whose writers synchronize on their own).
+///
+/// The backends spin, and every iteration of their loops is a branch of
+/// the model: two threads are enough, a third one makes it explode.
+#[cfg(any(feature = "spin", feature = "ticket"))]
+fn lock_backend<L: crate::lock::WriteLock + 'static>() {
+    loom::model(|| {
+        let lock = Arc::new((L::default(), UnsafeCell::new(0)));
+        let handle = thread::spawn({
+            let lock = Arc::clone(&lock);
+            move || {
+                lock.0.lock();
+                // SAFETY: we hold the lock.
+                lock.1.with_mut(|ptr| unsafe { *ptr += 1 });
+                // SAFETY: we hold the lock.
+                unsafe { lock.0.unlock() };
+            }
+        });
+        if lock.0.try_lock() {
+            // SAFETY: we hold the lock.
+            lock.1.with_mut(|ptr| unsafe { *ptr += 1 });
+            // SAFETY: we hold the lock.
+            unsafe { lock.0.unlock() };
+        }
+        lock.0.lock();
+        // SAFETY: we hold the lock.
+        lock.1.with_mut(|ptr| unsafe { *ptr += 1 });
+        // SAFETY: we hold the lock.
+        unsafe { lock.0.unlock() };
+
+        handle.join().unwrap();
+        // SAFETY: the writer is joined.
+        let count = lock.1.with(|ptr| unsafe { *ptr });
+        assert!(count == 2 || count == 3);
+    });
+}
+
+#[cfg(feature = "spin")]
+#[test]
+fn lock_spin() {
+    lock_backend::<crate::lock::SpinLock>();
+}
+
+#[cfg(feature = "ticket")]
+#[test]
+fn lock_ticket() {
+    lock_backend::<crate::lock::TicketLock>();
+}
+
 #[test]
 fn length_consistency_panic() {
     loom::model(|| {
@@ -74,3 +330,24 @@ fn length_consistency_panic() {
         assert_eq!(lock.len(), 1);
     });
 }
+
+/// Tests that a value found in the index of a [`GrowInterner`] is
+/// initialized
+#[test]
+fn interner_publication() {
+    loom::model(|| {
+        let interner = Arc::new(GrowInterner::with_capacity(1));
+        let writer = thread::spawn({
+            let interner = Arc::clone(&interner);
+            move || interner.intern(Tracked::new(42))
+        });
+        let reader = thread::spawn({
+            let interner = Arc::clone(&interner);
+            move || interner.get(&Tracked::new(42))
+        });
+
+        let id = writer.join().unwrap();
+        assert!(matches!(reader.join().unwrap(), None | Some(0)));
+        assert_eq!(id, 0);
+    });
+}
@@ -0,0 +1,185 @@
+//! Blocking (and asynchronous) wait for the `len` of a
+//! [`GrowLock`](crate::GrowLock).
+
+#[cfg(all(feature = "std", not(loom)))]
+use std::sync::{
+    Condvar, Mutex,
+    atomic::{self, AtomicUsize, Ordering},
+};
+#[cfg(feature = "async")]
+use std::{
+    mem,
+    task::{Context, Poll, Waker},
+};
+#[cfg(feature = "std")]
+use std::{sync::PoisonError, time::Instant};
+
+#[cfg(loom)]
+use loom::sync::{
+    Condvar, Mutex,
+    atomic::{self, AtomicUsize, Ordering},
+};
+
+/// Readers waiting for a [`GrowLock`](crate::GrowLock) to reach a given
+/// length.
+///
+/// # Protocol
+/// * a waiter registers itself in `count`, and then checks `len` while
+///   holding the `mutex`, before sleeping on the `condvar`.
+/// * a writer publishes `len`, and then checks `count`: only if someone is
+///   waiting it locks the `mutex` and wakes them up.
+///
+/// Both sides must observe the write of the other (this is the "store
+/// buffering" pattern), so each of them issues a `SeqCst` fence between
+/// its write and its read.
+///
+/// Asynchronous waiters follow the same protocol, registering their
+/// [`Waker`] in `wakers` instead of sleeping on the `condvar`.
+#[cfg(feature = "std")]
+pub(crate) struct Waiters {
+    /// Number of sleeping threads, plus the number of registered wakers.
+    count: AtomicUsize,
+    mutex: Mutex<()>,
+    condvar: Condvar,
+    #[cfg(feature = "async")]
+    wakers: Mutex<Vec<Waker>>,
+}
+
+#[cfg(feature = "std")]
+impl Waiters {
+    pub(crate) fn new() -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            mutex: Mutex::new(()),
+            condvar: Condvar::new(),
+            #[cfg(feature = "async")]
+            wakers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Blocks until `len` is at least `n` or `deadline` is reached,
+    /// returning the observed length in the first case.
+    pub(crate) fn wait(
+        &self,
+        len: &AtomicUsize,
+        n: usize,
+        deadline: Option<Instant>,
+    ) -> Option<usize> {
+        let current = len.load(Ordering::Acquire);
+        if current >= n {
+            return Some(current);
+        }
+
+        self.count.fetch_add(1, Ordering::Relaxed);
+        atomic::fence(Ordering::SeqCst);
+        // this mutex only protects the sleep, so poisoning is irrelevant.
+        let mut guard =
+            self.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = loop {
+            let current = len.load(Ordering::Acquire);
+            if current >= n {
+                break Some(current);
+            }
+            match deadline {
+                None => {
+                    guard = self
+                        .condvar
+                        .wait(guard)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break None;
+                    }
+                    guard = self
+                        .condvar
+                        .wait_timeout(guard, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+            }
+        };
+        drop(guard);
+        self.count.fetch_sub(1, Ordering::Relaxed);
+        result
+    }
+
+    /// Returns the length if it is at least `n`, otherwise registers the
+    /// waker of `cx` to be woken up by the next [`Waiters::notify`].
+    #[cfg(feature = "async")]
+    pub(crate) fn poll_wait(
+        &self,
+        len: &AtomicUsize,
+        n: usize,
+        cx: &Context<'_>,
+    ) -> Poll<usize> {
+        let current = len.load(Ordering::Acquire);
+        if current >= n {
+            return Poll::Ready(current);
+        }
+
+        {
+            let mut wakers =
+                self.wakers.lock().unwrap_or_else(PoisonError::into_inner);
+            // a future polled again before being woken up is already
+            // registered.
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+                self.count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        atomic::fence(Ordering::SeqCst);
+        // if the length is reached now, the waker stays registered until
+        // the next notification, which is harmless.
+        let current = len.load(Ordering::Acquire);
+        if current >= n {
+            Poll::Ready(current)
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Wakes up the waiters, if any. Must be called after publishing a new
+    /// `len`.
+    #[inline]
+    pub(crate) fn notify(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.count.load(Ordering::Relaxed) != 0 {
+            self.notify_slow();
+        }
+    }
+    #[cold]
+    fn notify_slow(&self) {
+        // a waiter may have checked `len` before our store but not be
+        // sleeping yet: taking the mutex waits for it to sleep.
+        drop(self.mutex.lock());
+        self.condvar.notify_all();
+
+        #[cfg(feature = "async")]
+        {
+            let wakers = mem::take(
+                &mut *self
+                    .wakers
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            );
+            self.count.fetch_sub(wakers.len(), Ordering::Relaxed);
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+/// Without `std` no one can wait, so there is no one to wake up.
+#[cfg(not(feature = "std"))]
+pub(crate) struct Waiters;
+
+#[cfg(not(feature = "std"))]
+impl Waiters {
+    pub(crate) const fn new() -> Self {
+        Self
+    }
+    #[inline]
+    #[allow(clippy::unused_self)]
+    pub(crate) fn notify(&self) {}
+}